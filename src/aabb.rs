@@ -13,6 +13,13 @@ impl AABB {
     }
 
     pub fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> bool {
+        self.hit_interval(r, tmin, tmax).is_some()
+    }
+
+    // Part of [tmin, tmax] during which the ray is inside the box
+    pub fn hit_interval(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<(f32, f32)> {
+        let mut tmin = tmin;
+        let mut tmax = tmax;
         for a in 0..3 {
             let inv_d: f32 = 1.0 / r.direction[a];
             let mut t0: f32 = (self.min[a] - r.origin[a]) * inv_d;
@@ -20,14 +27,62 @@ impl AABB {
             if inv_d < 0.0 {
                 std::mem::swap(&mut t0, &mut t1);
             }
-            let tmin: f32 = if t0 > tmin { t0 } else { tmin };
-            let tmax: f32 = if t1 < tmax { t1 } else { tmax };
+            tmin = if t0 > tmin { t0 } else { tmin };
+            tmax = if t1 < tmax { t1 } else { tmax };
             if tmax <= tmin {
+                return None;
+            }
+        }
+        Some((tmin, tmax))
+    }
+
+    // Same slab test as `hit`, but with the reciprocal of the ray direction
+    // computed once by the caller (used by BVH traversal)
+    pub fn hit_with_inverse(&self, origin: &Vec3, inv_d: &Vec3, tmin: f32, tmax: f32) -> bool {
+        let mut tmin = tmin;
+        let mut tmax = tmax;
+        for a in 0..3 {
+            let mut t0: f32 = (self.min[a] - origin[a]) * inv_d[a];
+            let mut t1: f32 = (self.max[a] - origin[a]) * inv_d[a];
+            if inv_d[a] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > tmin {
+                tmin = t0;
+            }
+            if t1 < tmax {
+                tmax = t1;
+            }
+            if tmax < tmin {
                 return false;
             }
         }
         true
     }
+
+    // Overlap of the two boxes, flat where they don't overlap
+    pub fn intersection(&self, other: &AABB) -> AABB {
+        let min = Vec3::new(
+            self.min.x.max(other.min.x),
+            self.min.y.max(other.min.y),
+            self.min.z.max(other.min.z),
+        );
+        let max = Vec3::new(
+            self.max.x.min(other.max.x).max(min.x),
+            self.max.y.min(other.max.y).max(min.y),
+            self.max.z.min(other.max.z).max(min.z),
+        );
+        AABB::new(min, max)
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d: Vec3 = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
 }
 
 pub fn surrounding_box(bbox0: AABB, bbox1: AABB) -> AABB {
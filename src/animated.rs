@@ -0,0 +1,262 @@
+use crate::aabb::{surrounding_box, AABB};
+use crate::hitable::{HitRecord, Hitable, Interval, Solid};
+use crate::matrix::Matrix4;
+use crate::quaternion::Quaternion;
+use crate::ray::Ray;
+use crate::transform::transform_box;
+use crate::vec3::Vec3;
+
+// Number of steps between two keyframes when bounding the motion
+const BOX_STEPS: usize = 8;
+
+// Pose of an object at a given time. The object is scaled first, then
+// rotated, then translated.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f32) -> Self {
+        Keyframe {
+            time,
+            translation: Vec3::zeros(),
+            rotation: Quaternion::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn translation(mut self, offset: Vec3) -> Self {
+        self.translation = offset;
+        self
+    }
+
+    pub fn rotation(mut self, axis: Vec3, angle: f32) -> Self {
+        self.rotation = Quaternion::from_axis_angle(axis, angle);
+        self
+    }
+
+    pub fn scale(mut self, factor: Vec3) -> Self {
+        self.scale = factor;
+        self
+    }
+
+    fn lerp(&self, other: &Keyframe, time: f32) -> Keyframe {
+        let f: f32 = (time - self.time) / (other.time - self.time);
+        Keyframe {
+            time,
+            translation: (1.0 - f) * self.translation + f * other.translation,
+            rotation: self.rotation.slerp(&other.rotation, f),
+            scale: (1.0 - f) * self.scale + f * other.scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::translation(self.translation)
+            * self.rotation.to_matrix()
+            * Matrix4::scaling(self.scale)
+    }
+
+    // Cheaper than `Matrix4::inverse`, since the pose is known to be TRS
+    pub fn inverse_matrix(&self) -> Matrix4 {
+        let inv_scale = Vec3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        Matrix4::scaling(inv_scale)
+            * self.rotation.to_matrix().transpose()
+            * Matrix4::translation(-self.translation)
+    }
+}
+
+// Moves any hitable along keyframes, so that rays at different times see it
+// in different places (motion blur). Between keyframes the translation and
+// scale are interpolated linearly and the rotation with slerp; before the
+// first or after the last keyframe the object stays still.
+pub struct AnimatedTransform<H: Hitable> {
+    pub hitable: H,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl<H: Hitable> AnimatedTransform<H> {
+    pub fn new(hitable: H, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "animation without keyframes");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        AnimatedTransform { hitable, keyframes }
+    }
+
+    pub fn pose_at(&self, time: f32) -> Keyframe {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return Keyframe { time, ..*first };
+        }
+        if time >= last.time {
+            return Keyframe { time, ..*last };
+        }
+        let i = self.keyframes.iter().rposition(|k| k.time <= time).unwrap();
+        self.keyframes[i].lerp(&self.keyframes[i + 1], time)
+    }
+
+    pub fn matrix_at(&self, time: f32) -> Matrix4 {
+        self.pose_at(time).matrix()
+    }
+}
+
+impl<H: Hitable> Hitable for AnimatedTransform<H> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let inverse = self.pose_at(r.time).inverse_matrix();
+        let rec = self.hitable.hit(&local_ray(&inverse, r), t_min, t_max)?;
+        Some(to_world(&inverse, r, rec))
+    }
+
+    // Boxes at sampled times only catch the object where it was at those
+    // times, so each one is grown by how far any point of the object can
+    // travel before the next sample. Keyframe times are always sampled,
+    // which keeps the motion between two samples a single smooth segment.
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        let bbox = self.hitable.bounding_box(t0, t1)?;
+        let radius: f32 = (0..8)
+            .map(|i| {
+                Vec3::new(
+                    if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                    if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                    if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+                )
+                .length()
+            })
+            .fold(0.0, f32::max);
+
+        let mut breaks: Vec<f32> = vec![t0];
+        breaks.extend(
+            self.keyframes
+                .iter()
+                .map(|k| k.time)
+                .filter(|&t| t > t0 && t < t1),
+        );
+        breaks.push(t1);
+
+        let mut result = transform_box(&self.matrix_at(t1), &bbox);
+        for w in breaks.windows(2) {
+            for i in 0..BOX_STEPS {
+                let ta: f32 = w[0] + (w[1] - w[0]) * i as f32 / BOX_STEPS as f32;
+                let tb: f32 = w[0] + (w[1] - w[0]) * (i + 1) as f32 / BOX_STEPS as f32;
+                let (a, b) = (self.pose_at(ta), self.pose_at(tb));
+
+                // |T(t) - T(ta)| + |(S(t) - S(ta)) p| + |(R(t) - R(ta)) S(ta) p|
+                let ds: Vec3 = b.scale - a.scale;
+                let max_scale: f32 = (0..3)
+                    .map(|c| a.scale[c].abs().max(b.scale[c].abs()))
+                    .fold(0.0, f32::max);
+                let max_ds: f32 = (0..3).map(|c| ds[c].abs()).fold(0.0, f32::max);
+                let pad: f32 = (b.translation - a.translation).length()
+                    + max_ds * radius
+                    + a.rotation.angle_to(&b.rotation) * max_scale * radius;
+
+                let sample = transform_box(&a.matrix(), &bbox);
+                let pad = Vec3::new(pad, pad, pad);
+                result = surrounding_box(result, AABB::new(sample.min - pad, sample.max + pad));
+            }
+        }
+        Some(result)
+    }
+}
+
+impl<H: Solid> Solid for AnimatedTransform<H> {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        let inverse = self.pose_at(r.time).inverse_matrix();
+        self.hitable
+            .intervals(&local_ray(&inverse, r))
+            .into_iter()
+            .map(|i| Interval {
+                enter: to_world(&inverse, r, i.enter),
+                exit: to_world(&inverse, r, i.exit),
+            })
+            .collect()
+    }
+}
+
+fn local_ray(inverse: &Matrix4, r: &Ray) -> Ray {
+    Ray::new(
+        inverse.transform_point(&r.origin),
+        inverse.transform_vector(&r.direction),
+        r.time,
+    )
+}
+
+fn to_world<'a>(inverse: &Matrix4, r: &Ray, mut rec: HitRecord<'a>) -> HitRecord<'a> {
+    rec.p = r.point_at_parameter(rec.t);
+    rec.normal = inverse
+        .transpose()
+        .transform_vector(&rec.normal)
+        .unit_vector();
+    rec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+    use crate::transform::Transform;
+
+    fn block() -> Block {
+        Block::new(
+            Vec3::new(-1.0, -0.5, -0.2),
+            Vec3::new(1.0, 0.5, 0.2),
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    fn animated() -> AnimatedTransform<Block> {
+        let y = Vec3::new(0.0, 1.0, 0.0);
+        AnimatedTransform::new(
+            block(),
+            vec![
+                Keyframe::new(1.0)
+                    .translation(Vec3::new(3.0, 1.0, 0.0))
+                    .rotation(y, 170.0)
+                    .scale(Vec3::new(2.0, 1.0, 1.0)),
+                Keyframe::new(0.0),
+                Keyframe::new(0.5)
+                    .translation(Vec3::new(1.0, 0.0, 0.0))
+                    .rotation(Vec3::new(1.0, 0.0, 1.0), 80.0),
+            ],
+        )
+    }
+
+    #[test]
+    fn same_hits_as_transform() {
+        let anim = animated();
+        for &time in &[0.0, 0.2, 0.5, 0.77, 1.0] {
+            let fixed = Transform::with_matrix(block(), anim.matrix_at(time));
+            for i in 0..50 {
+                let target = Vec3::new(i as f32 * 0.1 - 2.0, 0.3, -0.1 * i as f32 / 5.0);
+                let origin = Vec3::new(-1.0, 5.0, 7.0);
+                let r = Ray::new(origin, target - origin, time);
+                let a = anim.hit(&r, 0.001, f32::MAX);
+                let b = fixed.hit(&r, 0.001, f32::MAX);
+                assert_eq!(a.is_some(), b.is_some());
+                if let (Some(a), Some(b)) = (a, b) {
+                    assert!((a.t - b.t).abs() < 1e-4);
+                    assert!((a.normal - b.normal).length() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn conservative_bounding_box() {
+        let anim = animated();
+        let bbox = anim.bounding_box(0.1, 0.9).unwrap();
+        let inner = block().bounding_box(0.1, 0.9).unwrap();
+        for i in 0..=200 {
+            let time = 0.1 + 0.8 * i as f32 / 200.0;
+            let b = transform_box(&anim.matrix_at(time), &inner);
+            for c in 0..3 {
+                assert!(bbox.min[c] <= b.min[c] && b.max[c] <= bbox.max[c]);
+            }
+        }
+    }
+}
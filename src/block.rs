@@ -81,12 +81,36 @@ impl Block {
 }
 
 impl Hitable for Block {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        self.hitablelist.hit(&r, t_min, t_max)
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.hitablelist.hit(r, t_min, t_max)
     }
 
-    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         let bbox = AABB::new(self.pmin, self.pmax);
         Some(bbox)
     }
 }
+
+impl Solid for Block {
+    // A ray crosses the box at most once, so the span runs from the first
+    // face it meets to the last one
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        let mut hits: Vec<HitRecord> = self
+            .hitablelist
+            .hitables
+            .iter()
+            .filter_map(|face| face.hit(r, -f32::MAX, f32::MAX))
+            .collect();
+        if hits.len() < 2 {
+            return vec![];
+        }
+        hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+        let exit = hits.pop().unwrap();
+        let enter = hits.swap_remove(0);
+        if enter.t < exit.t {
+            vec![Interval { enter, exit }]
+        } else {
+            vec![]
+        }
+    }
+}
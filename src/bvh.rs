@@ -0,0 +1,627 @@
+use crate::aabb::*;
+use crate::hitable::*;
+use crate::hitable_list::HitableList;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use std::cmp::Ordering;
+use std::fmt;
+
+pub struct BVHNode {
+    pub left: Box<dyn Hitable>,
+    pub right: Option<Box<dyn Hitable>>, // `None` only when the whole tree holds a single hitable
+    pub bbox: AABB,
+    // hitables without a bounding box (e.g. infinite planes), only kept at
+    // the root and tested against every ray
+    pub unbounded: HitableList,
+}
+
+impl BVHNode {
+    pub fn new(list: HitableList, time0: f32, time1: f32) -> Self {
+        assert!(list.size() > 0, "BVHNode needs at least one hitable");
+        let (hitables, unbounded) = split_unbounded(list.hitables, time0, time1);
+        let mut node = if hitables.is_empty() {
+            // nothing to put in the tree, every ray goes to `unbounded`
+            BVHNode {
+                left: Box::new(HitableList { hitables: vec![] }),
+                right: None,
+                bbox: AABB::new(Vec3::zeros(), Vec3::zeros()),
+                unbounded: HitableList { hitables: vec![] },
+            }
+        } else {
+            BVHNode::build(hitables)
+        };
+        node.unbounded = HitableList {
+            hitables: unbounded,
+        };
+        node
+    }
+
+    fn build(mut hitables: Vec<(AABB, Box<dyn Hitable>)>) -> Self {
+        if hitables.len() == 1 {
+            let (bbox, left) = hitables.pop().unwrap();
+            return BVHNode {
+                left,
+                right: None,
+                bbox,
+                unbounded: HitableList { hitables: vec![] },
+            };
+        }
+
+        // split along the axis where the centers of the boxes spread the most
+        let axis = longest_axis(&hitables);
+        hitables.sort_by(|(a, _), (b, _)| {
+            let ca = a.min[axis] + a.max[axis];
+            let cb = b.min[axis] + b.max[axis];
+            ca.partial_cmp(&cb).unwrap_or(Ordering::Equal)
+        });
+        let rest = hitables.split_off(hitables.len() / 2);
+        let (left_box, left) = subtree(hitables);
+        let (right_box, right) = subtree(rest);
+
+        BVHNode {
+            left,
+            right: Some(right),
+            bbox: surrounding_box(left_box, right_box),
+            unbounded: HitableList { hitables: vec![] },
+        }
+    }
+}
+
+// Pairs the hitables that have a bounding box with it, and sets apart the
+// ones that don't
+#[allow(clippy::type_complexity)]
+fn split_unbounded(
+    hitables: Vec<Box<dyn Hitable>>,
+    time0: f32,
+    time1: f32,
+) -> (Vec<(AABB, Box<dyn Hitable>)>, Vec<Box<dyn Hitable>>) {
+    let mut bounded = Vec::with_capacity(hitables.len());
+    let mut unbounded = Vec::new();
+    for hitable in hitables {
+        match hitable.bounding_box(time0, time1) {
+            Some(bbox) => bounded.push((bbox, hitable)),
+            None => unbounded.push(hitable),
+        }
+    }
+    (bounded, unbounded)
+}
+
+fn subtree(mut hitables: Vec<(AABB, Box<dyn Hitable>)>) -> (AABB, Box<dyn Hitable>) {
+    if hitables.len() == 1 {
+        hitables.pop().unwrap()
+    } else {
+        let node = BVHNode::build(hitables);
+        (node.bbox, Box::new(node))
+    }
+}
+
+fn longest_axis(hitables: &[(AABB, Box<dyn Hitable>)]) -> usize {
+    let mut min = [f32::MAX; 3];
+    let mut max = [-f32::MAX; 3];
+    for (bbox, _) in hitables {
+        for a in 0..3 {
+            let c = bbox.min[a] + bbox.max[a];
+            min[a] = min[a].min(c);
+            max[a] = max[a].max(c);
+        }
+    }
+    let extent = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+    if extent[0] > extent[1] && extent[0] > extent[2] {
+        0
+    } else if extent[1] > extent[2] {
+        1
+    } else {
+        2
+    }
+}
+
+impl BVHNode {
+    fn hit_tree(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+        let left_rec = self.left.hit(r, t_min, t_max);
+        if let Some(right) = &self.right {
+            // the right child only has to beat the left hit, if any
+            let closest_so_far = left_rec.as_ref().map_or(t_max, |rec| rec.t);
+            if let Some(right_rec) = right.hit(r, t_min, closest_so_far) {
+                return Some(right_rec);
+            }
+        }
+        left_rec
+    }
+}
+
+impl Hitable for BVHNode {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let tree_rec = self.hit_tree(r, t_min, t_max);
+        let closest_so_far = tree_rec.as_ref().map_or(t_max, |rec| rec.t);
+        self.unbounded.hit(r, t_min, closest_so_far).or(tree_rec)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        if self.unbounded.size() > 0 {
+            None
+        } else {
+            Some(self.bbox)
+        }
+    }
+}
+
+// Parameters of the binned SAH builder
+const SAH_BUCKETS: usize = 12;
+const MAX_PRIMS_IN_LEAF: usize = 4;
+// cost of a traversal step, relative to one primitive intersection
+const TRAVERSAL_COST: f32 = 0.125;
+// below this depth splits fall back to the median so that traversal stays
+// within `STACK_SIZE`
+const MAX_SAH_DEPTH: usize = 64;
+const STACK_SIZE: usize = 128;
+
+// Node of a BVH flattened into an array in depth-first order. The first child
+// of an interior node is the node right after it.
+#[derive(Clone, Copy)]
+pub struct LinearBVHNode {
+    pub bbox: AABB,
+    // leaf: position of the first primitive in `BVHTree::indices`
+    // interior: index of the second child
+    pub offset: u32,
+    pub count: u32, // number of primitives, 0 for interior nodes
+    pub axis: u8,   // split axis of interior nodes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BVHStats {
+    pub primitive_count: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub max_depth: usize,
+    pub min_leaf_size: usize,
+    pub max_leaf_size: usize,
+    pub mean_leaf_size: f32,
+}
+
+impl fmt::Display for BVHStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} primitives, {} nodes, {} leaves, depth {}, leaf size {}..{} (mean {:.2})",
+            self.primitive_count,
+            self.node_count,
+            self.leaf_count,
+            self.max_depth,
+            self.min_leaf_size,
+            self.max_leaf_size,
+            self.mean_leaf_size
+        )
+    }
+}
+
+struct PrimitiveInfo {
+    index: usize,
+    bbox: AABB,
+    centroid: Vec3,
+}
+
+// Acceleration structure over anything that can give a bounding box for each
+// of its primitives. Leaves refer to primitives by their position in the
+// slice the tree was built from.
+pub struct BVHTree {
+    pub nodes: Vec<LinearBVHNode>,
+    pub indices: Vec<usize>,
+    pub stats: BVHStats,
+}
+
+impl BVHTree {
+    pub fn new(bboxes: &[AABB]) -> Self {
+        let mut primitives: Vec<PrimitiveInfo> = bboxes
+            .iter()
+            .enumerate()
+            .map(|(index, bbox)| PrimitiveInfo {
+                index,
+                bbox: *bbox,
+                centroid: bbox.centroid(),
+            })
+            .collect();
+        let mut tree = BVHTree {
+            nodes: Vec::with_capacity(2 * bboxes.len()),
+            indices: Vec::with_capacity(bboxes.len()),
+            stats: BVHStats {
+                primitive_count: bboxes.len(),
+                node_count: 0,
+                leaf_count: 0,
+                max_depth: 0,
+                min_leaf_size: 0,
+                max_leaf_size: 0,
+                mean_leaf_size: 0.0,
+            },
+        };
+        if !primitives.is_empty() {
+            tree.stats.min_leaf_size = usize::MAX;
+            tree.build_recursive(&mut primitives, 0);
+            tree.stats.mean_leaf_size =
+                tree.stats.primitive_count as f32 / tree.stats.leaf_count as f32;
+        }
+        tree
+    }
+
+    pub fn bounding_box(&self) -> Option<AABB> {
+        self.nodes.first().map(|node| node.bbox)
+    }
+
+    fn build_recursive(&mut self, primitives: &mut [PrimitiveInfo], depth: usize) -> usize {
+        let n = primitives.len();
+        let bbox = primitives[1..]
+            .iter()
+            .fold(primitives[0].bbox, |bbox, p| surrounding_box(bbox, p.bbox));
+        let node_idx = self.nodes.len();
+        self.nodes.push(LinearBVHNode {
+            bbox,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+        self.stats.node_count += 1;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        let centroid_box = primitives[1..].iter().fold(
+            AABB::new(primitives[0].centroid, primitives[0].centroid),
+            |cbox, p| surrounding_box(cbox, AABB::new(p.centroid, p.centroid)),
+        );
+        let axis = max_extent(&centroid_box);
+        let cmin = centroid_box.min[axis];
+        let cmax = centroid_box.max[axis];
+
+        // a single primitive, or several that can't be told apart by centroid
+        if n == 1 || cmax <= cmin {
+            self.make_leaf(node_idx, primitives);
+            return node_idx;
+        }
+
+        let mut split: Option<usize> = None;
+        if n > 2 && depth < MAX_SAH_DEPTH && bbox.surface_area() > 0.0 {
+            match sah_split(primitives, &bbox, axis, cmin, cmax) {
+                Some(mid) => split = Some(mid).filter(|&mid| mid > 0 && mid < n),
+                None => {
+                    self.make_leaf(node_idx, primitives);
+                    return node_idx;
+                }
+            }
+        }
+        let mid = split.unwrap_or_else(|| {
+            primitives.select_nth_unstable_by(n / 2, |a, b| {
+                a.centroid[axis]
+                    .partial_cmp(&b.centroid[axis])
+                    .unwrap_or(Ordering::Equal)
+            });
+            n / 2
+        });
+
+        let (left, right) = primitives.split_at_mut(mid);
+        self.build_recursive(left, depth + 1);
+        let second = self.build_recursive(right, depth + 1);
+        self.nodes[node_idx].offset = second as u32;
+        self.nodes[node_idx].axis = axis as u8;
+        node_idx
+    }
+
+    fn make_leaf(&mut self, node_idx: usize, primitives: &[PrimitiveInfo]) {
+        let node = &mut self.nodes[node_idx];
+        node.offset = self.indices.len() as u32;
+        node.count = primitives.len() as u32;
+        self.indices.extend(primitives.iter().map(|p| p.index));
+        self.stats.leaf_count += 1;
+        self.stats.min_leaf_size = self.stats.min_leaf_size.min(primitives.len());
+        self.stats.max_leaf_size = self.stats.max_leaf_size.max(primitives.len());
+    }
+
+    // Finds the closest hit, calling `hit_primitive(index, closest_so_far)` for
+    // the primitives of every leaf the ray enters.
+    pub fn hit<'a, F>(
+        &self,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+        mut hit_primitive: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f32) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_d = Vec3::new(
+            1.0 / r.direction.x,
+            1.0 / r.direction.y,
+            1.0 / r.direction.z,
+        );
+        let dir_is_neg = [inv_d.x < 0.0, inv_d.y < 0.0, inv_d.z < 0.0];
+
+        let mut closest_so_far: f32 = t_max;
+        let mut hit_anything: Option<HitRecord> = None;
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len: usize = 0;
+        let mut current: usize = 0;
+        loop {
+            let node = &self.nodes[current];
+            if node
+                .bbox
+                .hit_with_inverse(&r.origin, &inv_d, t_min, closest_so_far)
+            {
+                if node.count > 0 {
+                    let start = node.offset as usize;
+                    let end = start + node.count as usize;
+                    for &index in &self.indices[start..end] {
+                        if let Some(rec) = hit_primitive(index, closest_so_far) {
+                            closest_so_far = rec.t;
+                            hit_anything = Some(rec);
+                        }
+                    }
+                } else {
+                    // visit the child nearer to the ray origin first
+                    let (near, far) = if dir_is_neg[node.axis as usize] {
+                        (node.offset as usize, current + 1)
+                    } else {
+                        (current + 1, node.offset as usize)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+        hit_anything
+    }
+}
+
+fn max_extent(bbox: &AABB) -> usize {
+    let d: Vec3 = bbox.max - bbox.min;
+    if d.x > d.y && d.x > d.z {
+        0
+    } else if d.y > d.z {
+        1
+    } else {
+        2
+    }
+}
+
+// Returns where to partition `primitives` according to the surface area
+// heuristic, or `None` if keeping them all in one leaf is cheaper.
+fn sah_split(
+    primitives: &mut [PrimitiveInfo],
+    bbox: &AABB,
+    axis: usize,
+    cmin: f32,
+    cmax: f32,
+) -> Option<usize> {
+    let bucket_of = |centroid: f32| -> usize {
+        let b = (SAH_BUCKETS as f32 * (centroid - cmin) / (cmax - cmin)) as usize;
+        b.min(SAH_BUCKETS - 1)
+    };
+
+    let mut counts = [0usize; SAH_BUCKETS];
+    let mut bounds: [Option<AABB>; SAH_BUCKETS] = [None; SAH_BUCKETS];
+    for p in primitives.iter() {
+        let b = bucket_of(p.centroid[axis]);
+        counts[b] += 1;
+        bounds[b] = Some(bounds[b].map_or(p.bbox, |bb| surrounding_box(bb, p.bbox)));
+    }
+
+    // sweep from both ends to get the cost of splitting after each bucket
+    let mut costs = [0.0f32; SAH_BUCKETS - 1];
+    let mut count: usize = 0;
+    let mut acc: Option<AABB> = None;
+    for i in 0..SAH_BUCKETS - 1 {
+        count += counts[i];
+        acc = merge_boxes(acc, bounds[i]);
+        costs[i] = count as f32 * acc.map_or(0.0, |b| b.surface_area());
+    }
+    let mut count: usize = 0;
+    let mut acc: Option<AABB> = None;
+    for i in (1..SAH_BUCKETS).rev() {
+        count += counts[i];
+        acc = merge_boxes(acc, bounds[i]);
+        costs[i - 1] += count as f32 * acc.map_or(0.0, |b| b.surface_area());
+    }
+
+    let mut min_bucket: usize = 0;
+    for i in 1..SAH_BUCKETS - 1 {
+        if costs[i] < costs[min_bucket] {
+            min_bucket = i;
+        }
+    }
+    let min_cost = TRAVERSAL_COST + costs[min_bucket] / bbox.surface_area();
+    let leaf_cost = primitives.len() as f32;
+
+    if primitives.len() > MAX_PRIMS_IN_LEAF || min_cost < leaf_cost {
+        // partition in place: primitives in buckets up to `min_bucket` go left
+        let mut mid: usize = 0;
+        for i in 0..primitives.len() {
+            if bucket_of(primitives[i].centroid[axis]) <= min_bucket {
+                primitives.swap(i, mid);
+                mid += 1;
+            }
+        }
+        Some(mid)
+    } else {
+        None
+    }
+}
+
+fn merge_boxes(a: Option<AABB>, b: Option<AABB>) -> Option<AABB> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(surrounding_box(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+// BVH over the hitables of a `HitableList`, built with the surface area
+// heuristic and stored as a flat array of nodes.
+pub struct FlatBVH {
+    pub hitables: Vec<Box<dyn Hitable>>, // in the order indexed by `tree`
+    pub unbounded: HitableList,          // no bounding box, tested against every ray
+    pub tree: BVHTree,
+}
+
+impl FlatBVH {
+    pub fn new(list: HitableList, time0: f32, time1: f32) -> Self {
+        let (bounded, unbounded) = split_unbounded(list.hitables, time0, time1);
+        let (bboxes, hitables): (Vec<AABB>, Vec<Box<dyn Hitable>>) = bounded.into_iter().unzip();
+        FlatBVH {
+            hitables,
+            unbounded: HitableList {
+                hitables: unbounded,
+            },
+            tree: BVHTree::new(&bboxes),
+        }
+    }
+
+    pub fn stats(&self) -> BVHStats {
+        self.tree.stats
+    }
+}
+
+impl Hitable for FlatBVH {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let tree_rec = self.tree.hit(r, t_min, t_max, |index, closest_so_far| {
+            self.hitables[index].hit(r, t_min, closest_so_far)
+        });
+        let closest_so_far = tree_rec.as_ref().map_or(t_max, |rec| rec.t);
+        self.unbounded.hit(r, t_min, closest_so_far).or(tree_rec)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        if self.unbounded.size() > 0 {
+            None
+        } else {
+            self.tree.bounding_box()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::texture::ConstantTexture;
+
+    use rand::prelude::*;
+
+    fn random_world(rng: &mut StdRng) -> HitableList {
+        let mut hitables: Vec<Box<dyn Hitable>> = vec![];
+        for _ in 0..200 {
+            let center = Vec3::new(
+                rng.gen_range(-10.0, 10.0),
+                rng.gen_range(-10.0, 10.0),
+                rng.gen_range(-10.0, 10.0),
+            );
+            hitables.push(Box::new(Sphere::new(
+                center,
+                rng.gen_range(0.1, 1.0),
+                Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+            )));
+        }
+        for _ in 0..20 {
+            let pmin = Vec3::new(
+                rng.gen_range(-10.0, 10.0),
+                rng.gen_range(-10.0, 10.0),
+                rng.gen_range(-10.0, 10.0),
+            );
+            let size = Vec3::new(
+                rng.gen_range(0.1, 2.0),
+                rng.gen_range(0.1, 2.0),
+                rng.gen_range(0.1, 2.0),
+            );
+            hitables.push(Box::new(Block::new(
+                pmin,
+                pmin + size,
+                Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+            )));
+        }
+        HitableList { hitables }
+    }
+
+    fn random_ray(rng: &mut StdRng) -> Ray {
+        let origin = Vec3::new(
+            rng.gen_range(-15.0, 15.0),
+            rng.gen_range(-15.0, 15.0),
+            rng.gen_range(-15.0, 15.0),
+        );
+        let target = Vec3::new(
+            rng.gen_range(-10.0, 10.0),
+            rng.gen_range(-10.0, 10.0),
+            rng.gen_range(-10.0, 10.0),
+        );
+        Ray::new(origin, target - origin, 0.0)
+    }
+
+    #[test]
+    fn same_hits_as_list() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let list = random_world(&mut rng);
+        let mut rng = StdRng::seed_from_u64(1);
+        let bvh = BVHNode::new(random_world(&mut rng), 0.0, 1.0);
+
+        for _ in 0..2000 {
+            let r = random_ray(&mut rng);
+            let expected = list.hit(&r, 0.001, f32::MAX);
+            let actual = bvh.hit(&r, 0.001, f32::MAX);
+            assert_eq!(
+                expected.map(|rec| (rec.t, rec.p)),
+                actual.map(|rec| (rec.t, rec.p))
+            );
+        }
+    }
+
+    #[test]
+    fn single_hitable() {
+        let hitables: Vec<Box<dyn Hitable>> = vec![Box::new(Sphere::new(
+            Vec3::zeros(),
+            1.0,
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        ))];
+        let bvh = BVHNode::new(HitableList { hitables }, 0.0, 1.0);
+        let r = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert_eq!(bvh.hit(&r, 0.001, f32::MAX).map(|rec| rec.t), Some(4.0));
+    }
+
+    #[test]
+    fn flat_bvh_same_hits_as_list() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let list = random_world(&mut rng);
+        let mut rng = StdRng::seed_from_u64(2);
+        let bvh = FlatBVH::new(random_world(&mut rng), 0.0, 1.0);
+
+        for _ in 0..2000 {
+            let r = random_ray(&mut rng);
+            let expected = list.hit(&r, 0.001, f32::MAX);
+            let actual = bvh.hit(&r, 0.001, f32::MAX);
+            assert_eq!(
+                expected.map(|rec| (rec.t, rec.p)),
+                actual.map(|rec| (rec.t, rec.p))
+            );
+        }
+    }
+
+    #[test]
+    fn flat_bvh_stats() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let bvh = FlatBVH::new(random_world(&mut rng), 0.0, 1.0);
+        let stats = bvh.stats();
+        assert_eq!(stats.primitive_count, 220);
+        assert_eq!(stats.node_count, 2 * stats.leaf_count - 1);
+        assert_eq!(bvh.tree.indices.len(), 220);
+        assert!(stats.min_leaf_size >= 1);
+        assert!(stats.max_depth < MAX_SAH_DEPTH);
+    }
+}
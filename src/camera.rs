@@ -67,6 +67,7 @@ impl Camera {
                 - self.origin
                 - offset,
             time: self.time0 + rand::random::<f32>() * (self.time1 - self.time0),
+            wavelength: None,
         }
     }
 }
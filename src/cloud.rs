@@ -0,0 +1,249 @@
+use crate::aabb::{surrounding_box, AABB};
+use crate::bvh::BVHTree;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::Vec3;
+
+use std::sync::OnceLock;
+
+// Many small spheres sharing one material, like particles from a
+// simulation. Particles are kept in flat arrays with a BVH of their own
+// rather than as one `Sphere` each, built on first use so that the builder
+// methods don't each pay for it. Particle colors, if any, are available to
+// the material through `VertexColorTexture`. Whatever the BVH depends on
+// is private, so it can't go stale once built.
+pub struct SphereCloud<M: Material> {
+    centers: Vec<Vec3>,    // at `time0`
+    radius: f32,           // shared by all particles, unless `radii` is set
+    radii: Vec<f32>,       // empty, or one per particle
+    pub colors: Vec<Vec3>, // empty, or one per particle
+    velocities: Vec<Vec3>, // empty, or one per particle, per unit of time
+    time0: f32,
+    time1: f32,
+    pub material: M,
+    tree: OnceLock<BVHTree>,
+}
+
+impl<M: Material> SphereCloud<M> {
+    pub fn new(centers: Vec<Vec3>, radius: f32, material: M) -> Self {
+        SphereCloud {
+            centers,
+            radius,
+            radii: vec![],
+            colors: vec![],
+            velocities: vec![],
+            time0: 0.0,
+            time1: 0.0,
+            material,
+            tree: OnceLock::new(),
+        }
+    }
+
+    pub fn with_radii(mut self, radii: Vec<f32>) -> Self {
+        assert_eq!(radii.len(), self.centers.len());
+        self.radii = radii;
+        self.tree = OnceLock::new();
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Vec3>) -> Self {
+        assert_eq!(colors.len(), self.centers.len());
+        self.colors = colors;
+        self
+    }
+
+    // Motion blur: particles start from their centers at `time0` and move
+    // at a constant velocity, boxed for the whole `time0..time1` shutter
+    pub fn with_velocities(mut self, velocities: Vec<Vec3>, time0: f32, time1: f32) -> Self {
+        assert_eq!(velocities.len(), self.centers.len());
+        self.velocities = velocities;
+        self.time0 = time0;
+        self.time1 = time1;
+        self.tree = OnceLock::new();
+        self
+    }
+
+    pub fn centers(&self) -> &[Vec3] {
+        &self.centers
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn radii(&self) -> &[f32] {
+        &self.radii
+    }
+
+    pub fn velocities(&self) -> &[Vec3] {
+        &self.velocities
+    }
+
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    pub fn center_at_time(&self, i: usize, time: f32) -> Vec3 {
+        match self.velocities.get(i) {
+            Some(velocity) => self.centers[i] + (time - self.time0) * *velocity,
+            None => self.centers[i],
+        }
+    }
+
+    fn radius_of(&self, i: usize) -> f32 {
+        self.radii.get(i).cloned().unwrap_or(self.radius)
+    }
+
+    fn tree(&self) -> &BVHTree {
+        self.tree.get_or_init(|| self.build_tree())
+    }
+
+    fn build_tree(&self) -> BVHTree {
+        let bboxes: Vec<AABB> = (0..self.centers.len())
+            .map(|i| {
+                let r: f32 = self.radius_of(i);
+                let extent = Vec3::new(r, r, r);
+                let at = |time: f32| {
+                    let center: Vec3 = self.center_at_time(i, time);
+                    AABB::new(center - extent, center + extent)
+                };
+                if self.velocities.is_empty() {
+                    at(self.time0)
+                } else {
+                    surrounding_box(at(self.time0), at(self.time1))
+                }
+            })
+            .collect();
+        BVHTree::new(&bboxes)
+    }
+
+    fn hit_particle(&self, i: usize, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let center: Vec3 = self.center_at_time(i, r.time);
+        let radius: f32 = self.radius_of(i);
+        let oc: Vec3 = r.origin - center;
+        let a: f32 = r.direction.dot(&r.direction);
+        let b: f32 = oc.dot(&r.direction);
+        let c: f32 = oc.dot(&oc) - radius * radius;
+        let discriminant: f32 = b * b - a * c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+        let root: f32 = discriminant.sqrt();
+        let t: f32 = [(-b - root) / a, (-b + root) / a]
+            .iter()
+            .cloned()
+            .find(|&t| t > t_min && t < t_max)?;
+        let p: Vec3 = r.point_at_parameter(t);
+        let normal: Vec3 = (p - center) / radius;
+        let (u, v) = get_sphere_uv(&normal);
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p,
+            normal,
+            color: self.colors.get(i).cloned(),
+            material: &self.material,
+        })
+    }
+}
+
+impl<M: Material> Hitable for SphereCloud<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.tree().hit(r, t_min, t_max, |i, closest_so_far| {
+            self.hit_particle(i, r, t_min, closest_so_far)
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        self.tree().bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable_list::HitableList;
+    use crate::material::Lambertian;
+    use crate::sphere::{MovingSphere, Sphere};
+    use crate::texture::{ConstantTexture, Texture, VertexColorTexture};
+
+    use rand::prelude::*;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn random_vec(rng: &mut StdRng, range: f32) -> Vec3 {
+        Vec3::new(
+            rng.gen_range(-range, range),
+            rng.gen_range(-range, range),
+            rng.gen_range(-range, range),
+        )
+    }
+
+    #[test]
+    fn same_hits_as_spheres() {
+        let mut rng = StdRng::seed_from_u64(19);
+        let centers: Vec<Vec3> = (0..500).map(|_| random_vec(&mut rng, 5.0)).collect();
+        let radii: Vec<f32> = (0..500).map(|_| rng.gen_range(0.05, 0.3)).collect();
+        let velocities: Vec<Vec3> = (0..500).map(|_| random_vec(&mut rng, 1.0)).collect();
+        let colors: Vec<Vec3> = (0..500).map(|i| Vec3::new(i as f32, 0.0, 0.0)).collect();
+
+        let still = SphereCloud::new(centers.clone(), 0.1, white())
+            .with_radii(radii.clone())
+            .with_colors(colors);
+        let moving = SphereCloud::new(centers.clone(), 0.1, white())
+            .with_radii(radii.clone())
+            .with_velocities(velocities.clone(), 0.0, 1.0);
+        assert_eq!(still.len(), 500);
+
+        let mut spheres: Vec<Box<dyn Hitable>> = vec![];
+        let mut moving_spheres: Vec<Box<dyn Hitable>> = vec![];
+        for i in 0..500 {
+            spheres.push(Box::new(Sphere::new(centers[i], radii[i], white())));
+            moving_spheres.push(Box::new(MovingSphere::new(
+                centers[i],
+                centers[i] + velocities[i],
+                0.0,
+                1.0,
+                radii[i],
+                white(),
+            )));
+        }
+        let spheres = HitableList { hitables: spheres };
+        let moving_spheres = HitableList {
+            hitables: moving_spheres,
+        };
+
+        for _ in 0..500 {
+            let origin: Vec3 = random_vec(&mut rng, 8.0);
+            let target: Vec3 = random_vec(&mut rng, 4.0);
+            let r = Ray::new(origin, target - origin, rng.gen_range(0.0, 1.0));
+            let a = still.hit(&r, 0.001, f32::MAX);
+            let b = spheres.hit(&r, 0.001, f32::MAX);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(rec), Some(b)) = (a, b) {
+                assert!((rec.t - b.t).abs() < 1e-4);
+                // the color picks out the particle that was hit
+                let i = rec.color.unwrap().x as usize;
+                assert!(((rec.p - centers[i]).length() - radii[i]).abs() < 1e-3);
+                let texture = VertexColorTexture::new(0.0, 1.0, 0.0);
+                assert_eq!(texture.value_at(&rec), rec.color.unwrap());
+            }
+
+            let a = moving.hit(&r, 0.001, f32::MAX).map(|rec| rec.t);
+            let b = moving_spheres.hit(&r, 0.001, f32::MAX).map(|rec| rec.t);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                assert!((a - b).abs() < 1e-4);
+            }
+        }
+    }
+}
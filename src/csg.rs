@@ -0,0 +1,199 @@
+use crate::aabb::{surrounding_box, AABB};
+use crate::hitable::{HitRecord, Hitable, Interval, Solid};
+use crate::ray::Ray;
+
+pub enum CsgOp {
+    Union,
+    Intersection,
+    Difference, // `a` with `b` cut away
+}
+
+impl CsgOp {
+    fn contains(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// Constructive solid geometry node. Both children must be closed, and the
+// node is a `Solid` itself so that nodes can be nested.
+pub struct Csg<A: Solid, B: Solid> {
+    pub op: CsgOp,
+    pub a: A,
+    pub b: B,
+}
+
+impl<A: Solid, B: Solid> Csg<A, B> {
+    pub fn new(op: CsgOp, a: A, b: B) -> Self {
+        Csg { op, a, b }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Csg::new(CsgOp::Union, a, b)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Csg::new(CsgOp::Intersection, a, b)
+    }
+
+    pub fn difference(a: A, b: B) -> Self {
+        Csg::new(CsgOp::Difference, a, b)
+    }
+}
+
+impl<A: Solid, B: Solid> Solid for Csg<A, B> {
+    // Walks the boundaries of both children in order along the ray and keeps
+    // the ones where being inside the result changes
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        let a = self.a.intervals(r);
+        if a.is_empty() && !matches!(self.op, CsgOp::Union) {
+            return vec![];
+        }
+        let b = self.b.intervals(r);
+
+        // (from `a`, entering the child, record)
+        let mut events: Vec<(bool, bool, HitRecord)> = Vec::new();
+        for (from_a, intervals) in [(true, a), (false, b)] {
+            for i in intervals {
+                events.push((from_a, true, i.enter));
+                events.push((from_a, false, i.exit));
+            }
+        }
+        events.sort_by(|x, y| x.2.t.total_cmp(&y.2.t));
+
+        let (mut in_a, mut in_b) = (false, false);
+        let mut result = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        for (from_a, entering, mut rec) in events {
+            let was_inside = self.op.contains(in_a, in_b);
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let inside = self.op.contains(in_a, in_b);
+            if inside == was_inside {
+                continue;
+            }
+            // leaving `b` means entering a difference, so the normal of that
+            // surface has to be turned around
+            if entering != inside {
+                rec.normal = -rec.normal;
+            }
+            if inside {
+                enter = Some(rec);
+            } else if let Some(enter) = enter.take() {
+                result.push(Interval { enter, exit: rec });
+            }
+        }
+        result
+    }
+}
+
+impl<A: Solid, B: Solid> Hitable for Csg<A, B> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.intervals(r)
+            .into_iter()
+            .flat_map(|i| [i.enter, i.exit])
+            .find(|rec| rec.t > t_min && rec.t < t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        let a = self.a.bounding_box(t0, t1)?;
+        match self.op {
+            CsgOp::Union => Some(surrounding_box(a, self.b.bounding_box(t0, t1)?)),
+            CsgOp::Intersection => Some(a.intersection(&self.b.bounding_box(t0, t1)?)),
+            CsgOp::Difference => Some(a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::texture::ConstantTexture;
+    use crate::transform::Transform;
+    use crate::vec3::Vec3;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn sphere_with_hole() {
+        // a unit sphere with a thinner sphere cut out of its +z side
+        let solid = Csg::difference(
+            Sphere::new(Vec3::zeros(), 1.0, white()),
+            Sphere::new(Vec3::new(0.0, 0.0, 1.0), 0.5, white()),
+        );
+        let down_z = Vec3::new(0.0, 0.0, -1.0);
+
+        // along the axis the ray first meets the inside of the hole
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), down_z, 0.0);
+        let rec = solid.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        // away from the hole it is a plain sphere
+        let r = Ray::new(Vec3::new(0.0, 0.9, 5.0), down_z, 0.0);
+        let rec = solid.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.normal.z > 0.0);
+        assert!((rec.p.length() - 1.0).abs() < 1e-4);
+
+        // from inside the solid, the next surface is the exit
+        let r = Ray::new(Vec3::new(0.0, 0.0, 0.0), down_z, 0.0);
+        let rec = solid.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-4);
+        assert_near(rec.normal, down_z);
+    }
+
+    #[test]
+    fn nested_operations() {
+        // a rounded cube, moved, unioned with a sphere beside it
+        let rounded = Csg::intersection(
+            Block::new(
+                Vec3::new(-1.0, -1.0, -1.0),
+                Vec3::new(1.0, 1.0, 1.0),
+                white(),
+            ),
+            Sphere::new(Vec3::zeros(), 1.3, white()),
+        );
+        let solid = Csg::union(
+            Transform::new(rounded).translate(Vec3::new(2.0, 0.0, 0.0)),
+            Sphere::new(Vec3::new(-1.0, 0.0, 0.0), 1.0, white()),
+        );
+
+        let bbox = solid.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.min, Vec3::new(-2.0, -1.0, -1.0));
+        assert_near(bbox.max, Vec3::new(3.0, 1.0, 1.0));
+
+        // through the flat face of the cube
+        let r = Ray::new(Vec3::new(2.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = solid.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        // through a corner, which the sphere rounds off
+        let r = Ray::new(Vec3::new(2.9, 0.9, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = solid.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.t > 4.0);
+
+        // along x the ray crosses both solids, which makes two spans
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let spans = solid.intervals(&r);
+        let ts: Vec<(f32, f32)> = spans.iter().map(|i| (i.enter.t, i.exit.t)).collect();
+        assert_eq!(ts.len(), 2);
+        assert!((ts[0].0 - 3.0).abs() < 1e-4 && (ts[0].1 - 5.0).abs() < 1e-4);
+        assert!((ts[1].0 - 6.0).abs() < 1e-4 && (ts[1].1 - 8.0).abs() < 1e-4);
+    }
+}
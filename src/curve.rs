@@ -0,0 +1,448 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::hitable_list::HitableList;
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use rand;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveKind {
+    Flat,     // ribbon always facing the ray, for grass blades seen from afar
+    Cylinder, // same, with normals bent across it to look round, for hair
+}
+
+// Cubic Bézier curve swept with a width going linearly from `width0` at the
+// root to `width1` at the tip. `u` runs along the curve and `v` across it.
+pub struct Curve<M: Material> {
+    pub points: [Vec3; 4],
+    pub width0: f32,
+    pub width1: f32,
+    pub kind: CurveKind,
+    pub material: M,
+    max_depth: u32,
+}
+
+impl<M: Material> Curve<M> {
+    pub fn new(points: [Vec3; 4], width0: f32, width1: f32, kind: CurveKind, material: M) -> Self {
+        // subdivide until the segments are straight to within a fraction of
+        // the width (the same estimate as pbrt's)
+        let mut l0: f32 = 0.0;
+        for i in 0..2 {
+            let d: Vec3 = points[i] - 2.0 * points[i + 1] + points[i + 2];
+            l0 = l0.max(d.x.abs()).max(d.y.abs()).max(d.z.abs());
+        }
+        let eps: f32 = 0.05 * width0.max(width1);
+        let depth: f32 = (std::f32::consts::SQRT_2 * 6.0 * l0 / (8.0 * eps)).log2() / 2.0;
+        Curve {
+            points,
+            width0,
+            width1,
+            kind,
+            material,
+            max_depth: depth.round().clamp(0.0, 10.0) as u32,
+        }
+    }
+
+    pub fn point_at(&self, u: f32) -> Vec3 {
+        bezier(&self.points, u)
+    }
+
+    pub fn tangent_at(&self, u: f32) -> Vec3 {
+        let p = &self.points;
+        let s: f32 = 1.0 - u;
+        3.0 * (s * s * (p[1] - p[0]) + 2.0 * s * u * (p[2] - p[1]) + u * u * (p[3] - p[2]))
+    }
+
+    // Closest crossing within the part of the curve given by `cp` (in ray
+    // space, where the ray starts at the origin and runs along +z) covering
+    // `u0..u1`. Returns the depth along the ray and `u`.
+    fn intersect(
+        &self,
+        cp: &[Vec3; 4],
+        u0: f32,
+        u1: f32,
+        depth: u32,
+        z_min: f32,
+        z_max: f32,
+    ) -> Option<(f32, f32)> {
+        // the curve lies in the hull of its control points
+        let half: f32 = 0.5 * self.width0.max(self.width1);
+        let mut min = cp[0];
+        let mut max = cp[0];
+        for p in &cp[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        if min.x - half > 0.0
+            || max.x + half < 0.0
+            || min.y - half > 0.0
+            || max.y + half < 0.0
+            || min.z - half > z_max
+            || max.z + half < z_min
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let (first, second) = split(cp);
+            let um: f32 = 0.5 * (u0 + u1);
+            let near = self.intersect(&first, u0, um, depth - 1, z_min, z_max);
+            let z_max: f32 = near.map_or(z_max, |(z, _)| z);
+            return self
+                .intersect(&second, um, u1, depth - 1, z_min, z_max)
+                .or(near);
+        }
+
+        // the segment must be crossed between the lines perpendicular to the
+        // curve at both ends, so that neighbouring segments don't both count
+        let edge: f32 = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return None;
+        }
+        let edge: f32 = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return None;
+        }
+
+        // closest point of the segment to the ray, taken as a straight line
+        let (sx, sy) = (cp[3].x - cp[0].x, cp[3].y - cp[0].y);
+        let denom: f32 = sx * sx + sy * sy;
+        if denom == 0.0 {
+            return None;
+        }
+        let w: f32 = ((-cp[0].x * sx - cp[0].y * sy) / denom).clamp(0.0, 1.0);
+        let u: f32 = u0 + w * (u1 - u0);
+        let width: f32 = self.width0 + u * (self.width1 - self.width0);
+        let pc: Vec3 = bezier(cp, w);
+        if pc.x * pc.x + pc.y * pc.y > 0.25 * width * width {
+            return None;
+        }
+        if pc.z < z_min || pc.z > z_max {
+            return None;
+        }
+        Some((pc.z, u))
+    }
+}
+
+impl<M: Material> Hitable for Curve<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let speed: f32 = r.direction.length();
+        let dz: Vec3 = r.direction / speed;
+        let helper = if dz.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let dx: Vec3 = helper.cross(&dz).unit_vector();
+        let dy: Vec3 = dz.cross(&dx);
+        let to_ray_space = |p: &Vec3| {
+            let q: Vec3 = *p - r.origin;
+            Vec3::new(q.dot(&dx), q.dot(&dy), q.dot(&dz))
+        };
+        let cp = [
+            to_ray_space(&self.points[0]),
+            to_ray_space(&self.points[1]),
+            to_ray_space(&self.points[2]),
+            to_ray_space(&self.points[3]),
+        ];
+        // the ray space keeps lengths, so depth is `t` times the speed
+        let z_max: f32 = if t_max < f32::MAX / speed {
+            t_max * speed
+        } else {
+            f32::MAX
+        };
+        let (z, u) = self.intersect(&cp, 0.0, 1.0, self.max_depth, t_min * speed, z_max)?;
+        let t: f32 = z / speed;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let p: Vec3 = r.point_at_parameter(t);
+        let tangent: Vec3 = self.tangent_at(u).unit_vector();
+        // faces the ray, turned around the curve
+        let facing: Vec3 = -(dz - dz.dot(&tangent) * tangent).unit_vector();
+        let side: Vec3 = tangent.cross(&facing);
+        let width: f32 = self.width0 + u * (self.width1 - self.width0);
+        let across: f32 =
+            ((p - self.point_at(u)).dot(&side) / (0.5 * width).max(1e-8)).clamp(-1.0, 1.0);
+        let normal: Vec3 = match self.kind {
+            CurveKind::Flat => facing,
+            CurveKind::Cylinder => (1.0 - across * across).sqrt() * facing + across * side,
+        };
+        Some(HitRecord {
+            t,
+            u,
+            v: 0.5 * (across + 1.0),
+            p,
+            normal,
+            color: None,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let half: f32 = 0.5 * self.width0.max(self.width1);
+        let mut min = self.points[0];
+        let mut max = self.points[0];
+        for p in &self.points[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        let pad = Vec3::new(half, half, half);
+        Some(AABB::new(min - pad, max + pad))
+    }
+}
+
+fn bezier(p: &[Vec3; 4], u: f32) -> Vec3 {
+    let s: f32 = 1.0 - u;
+    s * s * s * p[0] + 3.0 * s * s * u * p[1] + 3.0 * s * u * u * p[2] + u * u * u * p[3]
+}
+
+// De Casteljau split at the middle
+fn split(p: &[Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    let p01: Vec3 = 0.5 * (p[0] + p[1]);
+    let p12: Vec3 = 0.5 * (p[1] + p[2]);
+    let p23: Vec3 = 0.5 * (p[2] + p[3]);
+    let p012: Vec3 = 0.5 * (p01 + p12);
+    let p123: Vec3 = 0.5 * (p12 + p23);
+    let mid: Vec3 = 0.5 * (p012 + p123);
+    ([p[0], p01, p012, mid], [mid, p123, p23, p[3]])
+}
+
+// How the strands scattered by `hair_on_sphere` and `hair_on_mesh` look
+#[derive(Debug, Clone, Copy)]
+pub struct HairStyle {
+    pub count: usize,
+    pub length: f32,
+    pub width: f32,     // at the root
+    pub tip_width: f32, // at the tip
+    pub droop: f32,     // how far the tip sags, as a fraction of `length`
+    pub jitter: f32,    // random tilt away from the surface normal
+    pub kind: CurveKind,
+}
+
+fn random_in_unit_sphere() -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            2.0 * rand::random::<f32>() - 1.0,
+            2.0 * rand::random::<f32>() - 1.0,
+            2.0 * rand::random::<f32>() - 1.0,
+        );
+        if p.squared_length() < 1.0 {
+            return p;
+        }
+    }
+}
+
+fn strand<M: Material + Clone + 'static>(
+    root: Vec3,
+    normal: Vec3,
+    style: &HairStyle,
+    material: &M,
+) -> Box<dyn Hitable> {
+    let dir: Vec3 = (normal + style.jitter * random_in_unit_sphere()).unit_vector();
+    let sag: Vec3 = Vec3::new(0.0, -style.droop * style.length, 0.0);
+    let l: f32 = style.length;
+    let points = [
+        root,
+        root + (l / 3.0) * dir,
+        root + (2.0 * l / 3.0) * dir + sag / 3.0,
+        root + l * dir + sag,
+    ];
+    Box::new(Curve::new(
+        points,
+        style.width,
+        style.tip_width,
+        style.kind,
+        material.clone(),
+    ))
+}
+
+// Strands growing out of a sphere, spread uniformly over its surface. Put
+// the result in a `bvh::FlatBVH` before rendering.
+pub fn hair_on_sphere<M: Material + Clone + 'static>(
+    center: Vec3,
+    radius: f32,
+    style: &HairStyle,
+    material: M,
+) -> HitableList {
+    let hitables = (0..style.count)
+        .map(|_| {
+            let normal: Vec3 = random_in_unit_sphere().unit_vector();
+            strand(center + radius * normal, normal, style, &material)
+        })
+        .collect();
+    HitableList { hitables }
+}
+
+// Strands growing out of the faces of a mesh, as many per unit of area
+// everywhere
+pub fn hair_on_mesh<M: Material + Clone + 'static, N: Material>(
+    mesh: &TriangleMesh<N>,
+    style: &HairStyle,
+    material: M,
+) -> HitableList {
+    let corners = |face: &[u32; 3]| {
+        [
+            mesh.positions[face[0] as usize],
+            mesh.positions[face[1] as usize],
+            mesh.positions[face[2] as usize],
+        ]
+    };
+    // running total of the face areas, to pick faces by area
+    let mut total: f32 = 0.0;
+    let cumulative: Vec<f32> = mesh
+        .indices
+        .iter()
+        .map(|face| {
+            let [v0, v1, v2] = corners(face);
+            total += 0.5 * (v1 - v0).cross(&(v2 - v0)).length();
+            total
+        })
+        .collect();
+
+    let hitables = (0..style.count)
+        .map(|_| {
+            let x: f32 = rand::random::<f32>() * total;
+            let face_idx = cumulative
+                .iter()
+                .position(|&c| c > x)
+                .unwrap_or(cumulative.len() - 1);
+            let [v0, v1, v2] = corners(&mesh.indices[face_idx]);
+            let (r1, r2) = (rand::random::<f32>().sqrt(), rand::random::<f32>());
+            let root: Vec3 = (1.0 - r1) * v0 + r1 * (1.0 - r2) * v1 + r1 * r2 * v2;
+            let normal: Vec3 = (v1 - v0).cross(&(v2 - v0)).unit_vector();
+            strand(root, normal, style, &material)
+        })
+        .collect();
+    HitableList { hitables }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn arc_crossings() {
+        // a round arch over the x axis, 0.2 wide at the root and 0.1 at the tip
+        let arch = Curve::new(
+            [
+                Vec3::new(-1.0, 0.0, 0.0),
+                Vec3::new(-1.0, 1.3, 0.0),
+                Vec3::new(1.0, 1.3, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+            ],
+            0.2,
+            0.1,
+            CurveKind::Cylinder,
+            white(),
+        );
+        let top: Vec3 = arch.point_at(0.5);
+        assert!((top.y - 0.975).abs() < 1e-5);
+
+        // straight through the top, then a little off the middle line
+        let forward = Vec3::new(0.0, 0.0, -1.0);
+        let r = Ray::new(Vec3::new(0.0, top.y, 5.0), forward, 0.0);
+        let rec = arch.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-3);
+        assert!((rec.u - 0.5).abs() < 1e-2);
+        assert!((rec.v - 0.5).abs() < 1e-2);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-2);
+
+        let r = Ray::new(Vec3::new(0.0, top.y + 0.05, 5.0), forward, 0.0);
+        let rec = arch.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.v > 0.8 || rec.v < 0.2);
+        assert!(rec.normal.y > 0.5);
+
+        // past the width, and under the arch
+        let r = Ray::new(Vec3::new(0.0, top.y + 0.1, 5.0), forward, 0.0);
+        assert!(arch.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.0, 0.5, 5.0), forward, 0.0);
+        assert!(arch.hit(&r, 0.001, f32::MAX).is_none());
+
+        // near the root, along the curve
+        let r = Ray::new(Vec3::new(-1.0, 0.05, 5.0), forward, 0.0);
+        let rec = arch.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!(rec.u < 0.1);
+    }
+
+    #[test]
+    fn fur_ball() {
+        let style = HairStyle {
+            count: 200,
+            length: 0.5,
+            width: 0.02,
+            tip_width: 0.005,
+            droop: 0.3,
+            jitter: 0.2,
+            kind: CurveKind::Cylinder,
+        };
+        let fur = hair_on_sphere(Vec3::new(0.0, 2.0, 0.0), 1.0, &style, white());
+        assert_eq!(fur.size(), 200);
+        let bbox = fur.bounding_box(0.0, 1.0).unwrap();
+        for a in 0..3 {
+            assert!(bbox.max[a] < 2.0 + 1.6 && bbox.min[a] > -2.0 - 1.6);
+        }
+    }
+
+    #[test]
+    fn hair_on_faces() {
+        // a floor facing +z and a wall facing +x, both of area 0.5
+        let mesh = TriangleMesh::new(
+            vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(2.0, 1.0, 0.0),
+                Vec3::new(2.0, 0.0, 1.0),
+            ],
+            vec![[0, 1, 2], [3, 4, 5]],
+            white(),
+        );
+        let style = HairStyle {
+            count: 100,
+            length: 1.0,
+            width: 0.01,
+            tip_width: 0.01,
+            droop: 0.0,
+            jitter: 0.0,
+            kind: CurveKind::Flat,
+        };
+        let hair = hair_on_mesh(&mesh, &style, white());
+        assert_eq!(hair.size(), 100);
+
+        // straight strands: thin boxes starting on a face and reaching one
+        // length out along its normal
+        let mut on_floor = 0;
+        for strand in &hair.hitables {
+            let bbox = strand.bounding_box(0.0, 1.0).unwrap();
+            let extent: Vec3 = bbox.max - bbox.min;
+            let axis: usize = if extent.z > extent.x { 2 } else { 0 };
+            let face: f32 = if axis == 2 { 0.0 } else { 2.0 };
+            assert!((bbox.min[axis] - face).abs() < 0.02);
+            assert!((extent[axis] - 1.0).abs() < 0.02);
+            for a in 0..3 {
+                assert!(a == axis || extent[a] < 0.02);
+            }
+            if axis == 2 {
+                on_floor += 1;
+            }
+        }
+        // faces get hair in proportion to their area
+        assert!(on_floor > 25 && on_floor < 75);
+    }
+}
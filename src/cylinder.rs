@@ -0,0 +1,310 @@
+use crate::aabb::AABB;
+use crate::disk::{azimuth, hit_ring};
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use std::f32::consts::PI;
+
+// Cylinder standing on the xz plane at `center` and reaching up `height`
+// along +y. With a partial sweep the cut sides are left open.
+pub struct Cylinder<M: Material> {
+    pub center: Vec3, // center of the bottom cap
+    pub radius: f32,
+    pub height: f32,
+    pub phi_max: f32, // sweep in radians
+    pub capped: bool,
+    pub material: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: M) -> Self {
+        Cylinder {
+            center,
+            radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: true,
+            material,
+        }
+    }
+
+    // Open tube, e.g. for pipes
+    pub fn without_caps(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+
+    // Keeps only the slice from +x around to `angle` degrees
+    pub fn with_sweep(mut self, angle: f32) -> Self {
+        self.phi_max = angle.to_radians().min(2.0 * PI);
+        self
+    }
+}
+
+impl<M: Material> Hitable for Cylinder<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let o: Vec3 = r.origin - self.center;
+        let d: Vec3 = r.direction;
+        let a: f32 = d.x * d.x + d.z * d.z;
+        let b: f32 = o.x * d.x + o.z * d.z;
+        let c: f32 = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        let roots = solve_quadratic(a, b, c);
+
+        let side = roots.iter().find_map(|&t| {
+            if t <= t_min || t >= t_max {
+                return None;
+            }
+            let p: Vec3 = o + t * d;
+            let phi: f32 = azimuth(p.x, p.z);
+            if p.y < 0.0 || p.y > self.height || phi > self.phi_max {
+                return None;
+            }
+            Some(HitRecord {
+                t,
+                u: phi / self.phi_max,
+                v: p.y / self.height,
+                p: r.point_at_parameter(t),
+                normal: Vec3::new(p.x, 0.0, p.z) / self.radius,
+                color: None,
+                material: &self.material,
+            })
+        });
+        if !self.capped {
+            return side;
+        }
+        let caps = [(0.0, self.radius), (self.height, self.radius)];
+        hit_caps(
+            &self.center,
+            &caps,
+            self.phi_max,
+            &self.material,
+            r,
+            t_min,
+            t_max,
+            side,
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(AABB::new(
+            self.center - Vec3::new(self.radius, 0.0, self.radius),
+            self.center + Vec3::new(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+// Cone standing on the xz plane at `center`, narrowing along +y to
+// `top_radius` at `height` (zero for a pointed cone)
+pub struct Cone<M: Material> {
+    pub center: Vec3, // center of the bottom cap
+    pub radius: f32,
+    pub top_radius: f32,
+    pub height: f32,
+    pub phi_max: f32, // sweep in radians
+    pub capped: bool,
+    pub material: M,
+}
+
+impl<M: Material> Cone<M> {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: M) -> Self {
+        Cone::frustum(center, radius, 0.0, height, material)
+    }
+
+    // Cone with its tip cut off, e.g. for lamp shades. `top_radius` may also
+    // be larger than `radius`.
+    pub fn frustum(center: Vec3, radius: f32, top_radius: f32, height: f32, material: M) -> Self {
+        Cone {
+            center,
+            radius,
+            top_radius,
+            height,
+            phi_max: 2.0 * PI,
+            capped: true,
+            material,
+        }
+    }
+
+    pub fn without_caps(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+
+    pub fn with_sweep(mut self, angle: f32) -> Self {
+        self.phi_max = angle.to_radians().min(2.0 * PI);
+        self
+    }
+}
+
+impl<M: Material> Hitable for Cone<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        // radius grows by `k` per unit of height: x^2 + z^2 = (radius + k y)^2
+        let k: f32 = (self.top_radius - self.radius) / self.height;
+        let o: Vec3 = r.origin - self.center;
+        let d: Vec3 = r.direction;
+        let s0: f32 = self.radius + k * o.y;
+        let a: f32 = d.x * d.x + d.z * d.z - k * k * d.y * d.y;
+        let b: f32 = o.x * d.x + o.z * d.z - s0 * k * d.y;
+        let c: f32 = o.x * o.x + o.z * o.z - s0 * s0;
+        let roots = solve_quadratic(a, b, c);
+
+        let side = roots.iter().find_map(|&t| {
+            if t <= t_min || t >= t_max {
+                return None;
+            }
+            let p: Vec3 = o + t * d;
+            let phi: f32 = azimuth(p.x, p.z);
+            if p.y < 0.0 || p.y > self.height || phi > self.phi_max {
+                return None;
+            }
+            let s: f32 = self.radius + k * p.y;
+            Some(HitRecord {
+                t,
+                u: phi / self.phi_max,
+                v: p.y / self.height,
+                p: r.point_at_parameter(t),
+                normal: Vec3::new(p.x, -s * k, p.z).unit_vector(),
+                color: None,
+                material: &self.material,
+            })
+        });
+        if !self.capped {
+            return side;
+        }
+        let caps = [(0.0, self.radius), (self.height, self.top_radius)];
+        hit_caps(
+            &self.center,
+            &caps,
+            self.phi_max,
+            &self.material,
+            r,
+            t_min,
+            t_max,
+            side,
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let radius: f32 = self.radius.max(self.top_radius);
+        Some(AABB::new(
+            self.center - Vec3::new(radius, 0.0, radius),
+            self.center + Vec3::new(radius, self.height, radius),
+        ))
+    }
+}
+
+// Roots of a t^2 + 2 b t + c, smallest first
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Vec<f32> {
+    if a.abs() < 1e-8 {
+        // only one crossing, when the ray is parallel to the side of a cone
+        return if b == 0.0 {
+            vec![]
+        } else {
+            vec![-c / (2.0 * b)]
+        };
+    }
+    let discriminant: f32 = b * b - a * c;
+    if discriminant <= 0.0 {
+        return vec![];
+    }
+    let t1: f32 = (-b - discriminant.sqrt()) / a;
+    let t2: f32 = (-b + discriminant.sqrt()) / a;
+    vec![t1.min(t2), t1.max(t2)]
+}
+
+// Closest of `closest` (the hit on the side, if any) and the caps, each given as
+// (height, radius). The bottom cap faces down and the top one up.
+#[allow(clippy::too_many_arguments)]
+fn hit_caps<'a>(
+    center: &Vec3,
+    caps: &[(f32, f32); 2],
+    phi_max: f32,
+    material: &'a dyn Material,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+    mut closest: Option<HitRecord<'a>>,
+) -> Option<HitRecord<'a>> {
+    for (i, &(y, radius)) in caps.iter().enumerate() {
+        if radius <= 0.0 {
+            continue;
+        }
+        let t_max: f32 = closest.as_ref().map_or(t_max, |rec| rec.t);
+        let cap_center: Vec3 = *center + Vec3::new(0.0, y, 0.0);
+        if let Some((t, u, v)) = hit_ring(&cap_center, 0.0, radius, phi_max, r, t_min, t_max) {
+            let normal = Vec3::new(0.0, if i == 0 { -1.0 } else { 1.0 }, 0.0);
+            closest = Some(HitRecord {
+                t,
+                u,
+                v,
+                p: r.point_at_parameter(t),
+                normal,
+                color: None,
+                material,
+            });
+        }
+    }
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn cylinder_side_and_caps() {
+        let cylinder = Cylinder::new(Vec3::new(0.0, 1.0, 0.0), 1.0, 2.0, white());
+        let r = Ray::new(Vec3::new(-5.0, 2.5, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = cylinder.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!((rec.u - 0.5).abs() < 1e-4 && (rec.v - 0.75).abs() < 1e-4);
+
+        let r = Ray::new(Vec3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = cylinder.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+
+        // an open tube is seen through, down to the inside of the far wall
+        let tube = Cylinder::new(Vec3::new(0.0, 1.0, 0.0), 1.0, 2.0, white()).without_caps();
+        let r = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.25, -1.0, 0.0), 0.0);
+        let rec = tube.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_near(rec.p, Vec3::new(1.0, 1.0, 0.0));
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        // half a cylinder, cut along the x axis
+        let half = Cylinder::new(Vec3::zeros(), 1.0, 1.0, white()).with_sweep(180.0);
+        let r = Ray::new(Vec3::new(0.0, 0.5, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let rec = half.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_near(rec.p, Vec3::new(0.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn cone_normals() {
+        let cone = Cone::new(Vec3::zeros(), 1.0, 1.0, white());
+        let r = Ray::new(Vec3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = cone.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(-1.0, 1.0, 0.0).unit_vector());
+
+        // a frustum wider at the top has its side facing down
+        let shade = Cone::frustum(Vec3::zeros(), 0.5, 1.0, 1.0, white()).without_caps();
+        let r = Ray::new(Vec3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = shade.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.25).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(-1.0, -0.5, 0.0).unit_vector());
+        let bbox = shade.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.max, Vec3::new(1.0, 1.0, 1.0));
+    }
+}
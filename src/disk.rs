@@ -0,0 +1,191 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use std::f32::consts::PI;
+
+// Angle around the y axis in [0, 2π), starting from +x
+pub fn azimuth(x: f32, z: f32) -> f32 {
+    let phi: f32 = z.atan2(x);
+    if phi < 0.0 {
+        phi + 2.0 * PI
+    } else {
+        phi
+    }
+}
+
+// Flat ring in the plane y = center.y between `inner_radius` and `radius`,
+// limited to azimuths up to `phi_max`. Returns `t`, `u` (around) and `v`
+// (from the outer edge inwards). Also used for the caps of cylinders and
+// cones.
+pub fn hit_ring(
+    center: &Vec3,
+    inner_radius: f32,
+    radius: f32,
+    phi_max: f32,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let t: f32 = (center.y - r.origin.y) / r.direction.y;
+    // also rejects rays parallel to the plane
+    if !(t_min..=t_max).contains(&t) {
+        return None;
+    }
+    let x: f32 = r.origin.x + t * r.direction.x - center.x;
+    let z: f32 = r.origin.z + t * r.direction.z - center.z;
+    let dist2: f32 = x * x + z * z;
+    if dist2 > radius * radius || dist2 < inner_radius * inner_radius {
+        return None;
+    }
+    let phi: f32 = azimuth(x, z);
+    if phi > phi_max {
+        return None;
+    }
+    let v: f32 = (radius - dist2.sqrt()) / (radius - inner_radius);
+    Some((t, phi / phi_max, v))
+}
+
+fn ring_box(center: &Vec3, radius: f32) -> AABB {
+    AABB::new(
+        *center - Vec3::new(radius, 0.0001, radius),
+        *center + Vec3::new(radius, 0.0001, radius),
+    )
+}
+
+// Disk facing +y
+pub struct Disk<M: Material> {
+    pub center: Vec3,
+    pub radius: f32,
+    pub phi_max: f32, // sweep in radians
+    pub material: M,
+}
+
+impl<M: Material> Disk<M> {
+    pub fn new(center: Vec3, radius: f32, material: M) -> Self {
+        Disk {
+            center,
+            radius,
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    // Keeps only the slice from +x around to `angle` degrees
+    pub fn with_sweep(mut self, angle: f32) -> Self {
+        self.phi_max = angle.to_radians().min(2.0 * PI);
+        self
+    }
+}
+
+impl<M: Material> Hitable for Disk<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t, u, v) = hit_ring(
+            &self.center,
+            0.0,
+            self.radius,
+            self.phi_max,
+            r,
+            t_min,
+            t_max,
+        )?;
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p: r.point_at_parameter(t),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            color: None,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(ring_box(&self.center, self.radius))
+    }
+}
+
+// Disk with a hole in the middle, facing +y
+pub struct Annulus<M: Material> {
+    pub center: Vec3,
+    pub inner_radius: f32,
+    pub radius: f32,
+    pub phi_max: f32, // sweep in radians
+    pub material: M,
+}
+
+impl<M: Material> Annulus<M> {
+    pub fn new(center: Vec3, inner_radius: f32, radius: f32, material: M) -> Self {
+        Annulus {
+            center,
+            inner_radius,
+            radius,
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    pub fn with_sweep(mut self, angle: f32) -> Self {
+        self.phi_max = angle.to_radians().min(2.0 * PI);
+        self
+    }
+}
+
+impl<M: Material> Hitable for Annulus<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t, u, v) = hit_ring(
+            &self.center,
+            self.inner_radius,
+            self.radius,
+            self.phi_max,
+            r,
+            t_min,
+            t_max,
+        )?;
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p: r.point_at_parameter(t),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            color: None,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(ring_box(&self.center, self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn annulus_hole_and_sweep() {
+        let white = Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5));
+        let ring = Annulus::new(Vec3::new(0.0, 1.0, 0.0), 0.5, 1.0, white).with_sweep(90.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+
+        // in the ring, a third of the way round the quarter
+        let (s, c) = (30.0_f32).to_radians().sin_cos();
+        let r = Ray::new(Vec3::new(0.75 * c, 3.0, 0.75 * s), down, 0.0);
+        let rec = ring.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-5);
+        assert!((rec.u - 1.0 / 3.0).abs() < 1e-4);
+        assert!((rec.v - 0.5).abs() < 1e-4);
+
+        // in the hole, outside the sweep, and parallel to the ring
+        let r = Ray::new(Vec3::new(0.2, 3.0, 0.2), down, 0.0);
+        assert!(ring.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(-0.7, 3.0, 0.1), down, 0.0);
+        assert!(ring.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(-2.0, 1.0, 0.1), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(ring.hit(&r, 0.001, f32::MAX).is_none());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::mesh::{vertex_normals, weld, MeshData};
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+use std::collections::HashMap;
+
+// Gives up splitting after this many rounds, each halving the long edges
+const MAX_ROUNDS: usize = 16;
+
+// Splits every edge longer than `max_edge` at its middle, round after round
+// until none is left. Whether an edge is split depends on the edge alone, so
+// both faces along it agree and no cracks open. Vertex attributes are
+// interpolated at the new vertices.
+pub fn tessellate(data: &MeshData, max_edge: f32) -> MeshData {
+    let mut data: MeshData = data.clone();
+    // split faces no longer match the polygons they came from
+    data.polygons.clear();
+    for _ in 0..MAX_ROUNDS {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut split = |data: &mut MeshData, a: u32, b: u32| -> Option<u32> {
+            let (pa, pb) = (data.positions[a as usize], data.positions[b as usize]);
+            if (pb - pa).length() <= max_edge {
+                return None;
+            }
+            let key = (a.min(b), a.max(b));
+            Some(*midpoints.entry(key).or_insert_with(|| {
+                let (a, b) = (a as usize, b as usize);
+                data.positions.push(0.5 * (pa + pb));
+                if !data.normals.is_empty() {
+                    let n: Vec3 = data.normals[a] + data.normals[b];
+                    data.normals.push(if n.squared_length() > 0.0 {
+                        n.unit_vector()
+                    } else {
+                        data.normals[a]
+                    });
+                }
+                if !data.uvs.is_empty() {
+                    let ((u0, v0), (u1, v1)) = (data.uvs[a], data.uvs[b]);
+                    data.uvs.push((0.5 * (u0 + u1), 0.5 * (v0 + v1)));
+                }
+                if !data.colors.is_empty() {
+                    let c: Vec3 = 0.5 * (data.colors[a] + data.colors[b]);
+                    data.colors.push(c);
+                }
+                (data.positions.len() - 1) as u32
+            }))
+        };
+
+        let faces: Vec<[u32; 3]> = std::mem::take(&mut data.indices);
+        let mut indices: Vec<[u32; 3]> = Vec::with_capacity(faces.len());
+        for &[a, b, c] in &faces {
+            let mids = [
+                split(&mut data, a, b),
+                split(&mut data, b, c),
+                split(&mut data, c, a),
+            ];
+            match mids {
+                [None, None, None] => indices.push([a, b, c]),
+                [Some(ab), None, None] => indices.extend(&[[a, ab, c], [ab, b, c]]),
+                [None, Some(bc), None] => indices.extend(&[[b, bc, a], [bc, c, a]]),
+                [None, None, Some(ca)] => indices.extend(&[[c, ca, b], [ca, a, b]]),
+                [Some(ab), Some(bc), None] => {
+                    indices.extend(&[[ab, b, bc], [a, ab, bc], [a, bc, c]])
+                }
+                [None, Some(bc), Some(ca)] => {
+                    indices.extend(&[[bc, c, ca], [b, bc, ca], [b, ca, a]])
+                }
+                [Some(ab), None, Some(ca)] => {
+                    indices.extend(&[[ca, a, ab], [c, ca, ab], [c, ab, b]])
+                }
+                [Some(ab), Some(bc), Some(ca)] => {
+                    indices.extend(&[[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]])
+                }
+            }
+        }
+        let done: bool = indices.len() == faces.len();
+        data.indices = indices;
+        if done {
+            break;
+        }
+    }
+    data
+}
+
+// Moves every vertex along its normal by `scale` times the brightness of
+// `texture` there (the average of its channels), after tessellating to
+// `max_edge` so that the detail of the texture shows in the geometry. The
+// texture is looked up with the vertex uvs, if any, and its position before
+// displacement, so solid textures like `NoiseTexture` need no uvs. Normals
+// are rebuilt from the displaced surface.
+pub fn displace<T: Texture>(data: &MeshData, texture: &T, scale: f32, max_edge: f32) -> MeshData {
+    let mut data: MeshData = data.clone();
+    if data.normals.is_empty() {
+        data.normals = vertex_normals(&data.positions, &data.indices);
+    }
+    let mut data: MeshData = tessellate(&data, max_edge);
+
+    // copies of a vertex along uv seams and hard edges all move the same
+    // way, along their mean normal and by the height at the first of them
+    let (points, point_of) = weld(&data.positions);
+    let mut normals: Vec<Vec3> = vec![Vec3::zeros(); points.len()];
+    let mut heights: Vec<Option<f32>> = vec![None; points.len()];
+    for (i, &point) in point_of.iter().enumerate() {
+        let point: usize = point as usize;
+        normals[point] += data.normals[i];
+        if heights[point].is_none() {
+            let (u, v) = data.uvs.get(i).cloned().unwrap_or((0.0, 0.0));
+            let color: Vec3 = texture.value(u, v, &points[point]);
+            heights[point] = Some((color.x + color.y + color.z) / 3.0);
+        }
+    }
+    for (p, &point) in data.positions.iter_mut().zip(&point_of) {
+        let point: usize = point as usize;
+        let normal: Vec3 = if normals[point].squared_length() > 0.0 {
+            normals[point].unit_vector()
+        } else {
+            normals[point]
+        };
+        *p = points[point] + scale * heights[point].unwrap_or(0.0) * normal;
+    }
+    data.normals = vertex_normals(&data.positions, &data.indices);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::ConstantTexture;
+
+    fn square() -> MeshData {
+        MeshData {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 2.0),
+                Vec3::new(2.0, 0.0, 2.0),
+                Vec3::new(2.0, 0.0, 0.0),
+            ],
+            uvs: vec![(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        }
+    }
+
+    #[test]
+    fn edges_split_without_cracks() {
+        let data = tessellate(&square(), 0.3);
+        let length =
+            |a: u32, b: u32| (data.positions[a as usize] - data.positions[b as usize]).length();
+        let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+        let mut area: f32 = 0.0;
+        for &[a, b, c] in &data.indices {
+            assert!(length(a, b) <= 0.3 && length(b, c) <= 0.3 && length(c, a) <= 0.3);
+            for &(p, q) in &[(a, b), (b, c), (c, a)] {
+                *edges.entry((p.min(q), p.max(q))).or_insert(0) += 1;
+            }
+            let [pa, pb, pc] = [a, b, c].map(|i| data.positions[i as usize]);
+            let n: Vec3 = (pb - pa).cross(&(pc - pa));
+            assert!(n.y > 0.0);
+            area += 0.5 * n.length();
+        }
+        assert!((area - 4.0).abs() < 1e-4);
+        // every inner edge shared by two faces, and the border 8 long
+        let border: f32 = edges
+            .iter()
+            .filter(|(_, &count)| count == 1)
+            .map(|(&(a, b), _)| length(a, b))
+            .sum();
+        assert!(edges.values().all(|&count| count <= 2));
+        assert!((border - 8.0).abs() < 1e-4);
+        for (p, &(u, v)) in data.positions.iter().zip(&data.uvs) {
+            assert!((p.x - 2.0 * u).abs() < 1e-5 && (p.z - 2.0 * v).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn raised_square() {
+        let data = displace(&square(), &ConstantTexture::new(0.2, 0.5, 0.8), 2.0, 0.5);
+        assert!(data.indices.len() > 2);
+        for (p, n) in data.positions.iter().zip(&data.normals) {
+            assert!((p.y - 1.0).abs() < 1e-5);
+            assert!((*n - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn hard_edges_stay_closed() {
+        // a cube with its own vertices and normal on every side, as loaded
+        // from a file with flat shading
+        let mut cube = MeshData::default();
+        for axis in 0..3 {
+            for &side in &[-1.0, 1.0] {
+                let mut normal = Vec3::zeros();
+                normal[axis] = side;
+                let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+                let first = cube.positions.len() as u32;
+                for &(s, t) in &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                    let mut p: Vec3 = normal;
+                    p[a] = s;
+                    p[b] = t;
+                    cube.positions.push(p);
+                    cube.normals.push(normal);
+                }
+                cube.indices.push([first, first + 1, first + 2]);
+                cube.indices.push([first, first + 2, first + 3]);
+            }
+        }
+
+        let before: MeshData = tessellate(&cube, 0.5);
+        let after: MeshData = displace(&cube, &ConstantTexture::new(1.0, 1.0, 1.0), 0.2, 0.5);
+        assert_eq!(before.positions.len(), after.positions.len());
+        for i in 0..before.positions.len() {
+            assert!(after.positions[i].length() > before.positions[i].length());
+            for j in 0..i {
+                if before.positions[i] == before.positions[j] {
+                    assert!((after.positions[i] - after.positions[j]).length() < 1e-6);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::perlin::Perlin;
+use crate::ray::Ray;
+use crate::triangle::{interpolate, intersect_triangle};
+use crate::vec3::Vec3;
+
+use image;
+use std::path::Path;
+
+// Terrain over the rectangle `min.x..min.x + size.x`, `min.z..min.z + size.z`
+// from a grid of `nx` by `nz` height samples in [0, 1], scaled to
+// `min.y..min.y + size.y`. Each grid cell is two triangles, but they are
+// only built on the fly for the cells a ray passes over.
+pub struct Heightfield<M: Material> {
+    pub heights: Vec<f32>, // row by row along x, rows following z
+    pub nx: usize,
+    pub nz: usize,
+    pub min: Vec3,
+    pub size: Vec3,
+    normals: Vec<Vec3>, // smoothed, one per sample
+    bbox: AABB,
+    pub material: M,
+}
+
+impl<M: Material> Heightfield<M> {
+    pub fn new(
+        heights: Vec<f32>,
+        nx: usize,
+        nz: usize,
+        min: Vec3,
+        size: Vec3,
+        material: M,
+    ) -> Self {
+        assert!(
+            nx >= 2 && nz >= 2,
+            "heightfield needs at least 2 x 2 samples"
+        );
+        assert_eq!(heights.len(), nx * nz, "heightfield sample count");
+
+        let (low, high) = heights
+            .iter()
+            .fold((f32::MAX, -f32::MAX), |(lo, hi), &h| (lo.min(h), hi.max(h)));
+        let bbox = AABB::new(
+            Vec3::new(min.x, min.y + low * size.y - 0.0001, min.z),
+            Vec3::new(
+                min.x + size.x,
+                min.y + high * size.y + 0.0001,
+                min.z + size.z,
+            ),
+        );
+        let mut field = Heightfield {
+            heights,
+            nx,
+            nz,
+            min,
+            size,
+            normals: vec![],
+            bbox,
+            material,
+        };
+        field.normals = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| field.sample_normal(i, j))
+            .collect();
+        field
+    }
+
+    // Grayscale of an image, with the top row of the image at `min.z`
+    pub fn from_image<P: AsRef<Path>>(
+        path: P,
+        min: Vec3,
+        size: Vec3,
+        material: M,
+    ) -> Result<Self, image::ImageError> {
+        let image_data = image::open(path)?.to_luma();
+        let (nx, nz) = image_data.dimensions();
+        let heights: Vec<f32> = image_data
+            .into_raw()
+            .iter()
+            .map(|&l| l as f32 / 255.0)
+            .collect();
+        Ok(Heightfield::new(
+            heights,
+            nx as usize,
+            nz as usize,
+            min,
+            size,
+            material,
+        ))
+    }
+
+    // Perlin turbulence sampled every `1 / frequency` units, rescaled so that
+    // the highest peak reaches `min.y + size.y`
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_noise(
+        perlin: &Perlin,
+        frequency: f32,
+        depth: usize,
+        nx: usize,
+        nz: usize,
+        min: Vec3,
+        size: Vec3,
+        material: M,
+    ) -> Self {
+        let mut heights: Vec<f32> = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let x: f32 = size.x * i as f32 / (nx - 1) as f32;
+                let z: f32 = size.z * j as f32 / (nz - 1) as f32;
+                heights.push(perlin.turbulence(&(frequency * Vec3::new(x, 0.0, z)), depth));
+            }
+        }
+        let peak: f32 = heights.iter().cloned().fold(0.0, f32::max);
+        if peak > 0.0 {
+            for h in heights.iter_mut() {
+                *h /= peak;
+            }
+        }
+        Heightfield::new(heights, nx, nz, min, size, material)
+    }
+
+    fn cell_size(&self) -> (f32, f32) {
+        (
+            self.size.x / (self.nx - 1) as f32,
+            self.size.z / (self.nz - 1) as f32,
+        )
+    }
+
+    fn position(&self, i: usize, j: usize) -> Vec3 {
+        let (dx, dz) = self.cell_size();
+        Vec3::new(
+            self.min.x + i as f32 * dx,
+            self.min.y + self.heights[j * self.nx + i] * self.size.y,
+            self.min.z + j as f32 * dz,
+        )
+    }
+
+    // Central differences, one sided at the border
+    fn sample_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+        let along_x: Vec3 = self.position(i1, j) - self.position(i0, j);
+        let along_z: Vec3 = self.position(i, j1) - self.position(i, j0);
+        along_z.cross(&along_x).unit_vector()
+    }
+
+    // The two triangles of cell (i, j), wound so that they face up
+    fn hit_cell(
+        &self,
+        i: usize,
+        j: usize,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let corners = [(i, j), (i, j + 1), (i + 1, j), (i + 1, j + 1)];
+        let mut closest: Option<HitRecord> = None;
+        for tri in [[0, 1, 2], [2, 1, 3]] {
+            let [a, b, c] = tri.map(|k| corners[k]);
+            let (v0, v1, v2) = (
+                self.position(a.0, a.1),
+                self.position(b.0, b.1),
+                self.position(c.0, c.1),
+            );
+            let t_max: f32 = closest.as_ref().map_or(t_max, |rec| rec.t);
+            if let Some((t, b1, b2)) = intersect_triangle(r, &v0, &v1, &v2, t_min, t_max) {
+                let normal: Vec3 = interpolate(
+                    &self.normals[a.1 * self.nx + a.0],
+                    &self.normals[b.1 * self.nx + b.0],
+                    &self.normals[c.1 * self.nx + c.0],
+                    b1,
+                    b2,
+                );
+                let p: Vec3 = r.point_at_parameter(t);
+                closest = Some(HitRecord {
+                    t,
+                    u: (p.x - self.min.x) / self.size.x,
+                    v: 1.0 - (p.z - self.min.z) / self.size.z,
+                    p,
+                    normal: normal.unit_vector(),
+                    color: None,
+                    material: &self.material,
+                });
+            }
+        }
+        closest
+    }
+}
+
+impl<M: Material> Hitable for Heightfield<M> {
+    // Walks the cells under the ray in order (2D DDA), so the first cell
+    // with a hit holds the closest one
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (start, end) = self.bbox.hit_interval(r, t_min, t_max)?;
+        let (dx, dz) = self.cell_size();
+        let p: Vec3 = r.point_at_parameter(start);
+        let cell = |x: f32, d: f32, n: usize| ((x / d).floor().max(0.0) as usize).min(n - 2);
+        let mut i: usize = cell(p.x - self.min.x, dx, self.nx);
+        let mut j: usize = cell(p.z - self.min.z, dz, self.nz);
+
+        // `t` at the next cell border, and between two borders, per axis
+        let next = |o: f32, d: f32, lo: f32, size: f32, k: usize| -> (f32, f32) {
+            if d > 0.0 {
+                ((lo + (k + 1) as f32 * size - o) / d, size / d)
+            } else if d < 0.0 {
+                ((lo + k as f32 * size - o) / d, -size / d)
+            } else {
+                (f32::MAX, f32::MAX)
+            }
+        };
+        let (mut next_x, delta_x) = next(r.origin.x, r.direction.x, self.min.x, dx, i);
+        let (mut next_z, delta_z) = next(r.origin.z, r.direction.z, self.min.z, dz, j);
+
+        loop {
+            if let Some(rec) = self.hit_cell(i, j, r, t_min, t_max) {
+                return Some(rec);
+            }
+            if next_x < next_z {
+                if next_x > end
+                    || (r.direction.x > 0.0 && i + 2 >= self.nx)
+                    || (r.direction.x < 0.0 && i == 0)
+                {
+                    return None;
+                }
+                i = if r.direction.x > 0.0 { i + 1 } else { i - 1 };
+                next_x += delta_x;
+            } else {
+                if next_z > end
+                    || (r.direction.z > 0.0 && j + 2 >= self.nz)
+                    || (r.direction.z < 0.0 && j == 0)
+                {
+                    return None;
+                }
+                j = if r.direction.z > 0.0 { j + 1 } else { j - 1 };
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::mesh::TriangleMesh;
+    use crate::texture::ConstantTexture;
+    use rand::prelude::*;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn same_hits_as_mesh() {
+        let terrain = Heightfield::from_noise(
+            &Perlin::new(),
+            0.4,
+            4,
+            17,
+            11,
+            Vec3::new(-4.0, -1.0, -3.0),
+            Vec3::new(8.0, 2.0, 6.0),
+            white(),
+        );
+        let mut positions = Vec::new();
+        for j in 0..terrain.nz {
+            for i in 0..terrain.nx {
+                positions.push(terrain.position(i, j));
+            }
+        }
+        let mut indices = Vec::new();
+        for j in 0..terrain.nz as u32 - 1 {
+            for i in 0..terrain.nx as u32 - 1 {
+                let at = |i: u32, j: u32| j * terrain.nx as u32 + i;
+                indices.push([at(i, j), at(i, j + 1), at(i + 1, j)]);
+                indices.push([at(i + 1, j), at(i, j + 1), at(i + 1, j + 1)]);
+            }
+        }
+        let mesh = TriangleMesh::new(positions, indices, white());
+
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..500 {
+            let origin = Vec3::new(
+                rng.gen_range(-6.0, 6.0),
+                rng.gen_range(-0.5, 4.0),
+                rng.gen_range(-5.0, 5.0),
+            );
+            let target = Vec3::new(rng.gen_range(-4.0, 4.0), 0.0, rng.gen_range(-3.0, 3.0));
+            let r = Ray::new(origin, target - origin, 0.0);
+            let a = terrain.hit(&r, 0.001, f32::MAX);
+            let b = mesh.hit(&r, 0.001, f32::MAX);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                assert!((a.t - b.t).abs() < 1e-4);
+                assert!(a.normal.y > 0.0);
+                assert!((0.0..=1.0).contains(&a.u) && (0.0..=1.0).contains(&a.v));
+            }
+        }
+    }
+}
@@ -3,6 +3,8 @@ use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 
+use std::sync::Arc;
+
 #[derive(Clone)]
 pub struct HitRecord<'a> {
     pub t: f32,
@@ -10,11 +12,40 @@ pub struct HitRecord<'a> {
     pub v: f32,
     pub p: Vec3,
     pub normal: Vec3,
+    pub color: Option<Vec3>, // per-vertex color, for geometry that carries one
     pub material: &'a dyn Material,
 }
 
 // `Hitalbe` trait needs `Send` and `Sync` for `rayon` parallel processing.
 pub trait Hitable: Send + Sync {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB>;
 }
+
+// Shared geometry, so that one prototype can be placed many times (see
+// `instance::Instances`)
+impl<H: Hitable + ?Sized> Hitable for Arc<H> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        (**self).bounding_box(t0, t1)
+    }
+}
+
+// Span of a ray inside a closed object, from where it enters to where it
+// leaves. Both records keep outward facing normals.
+#[derive(Clone)]
+pub struct Interval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+// Closed objects that can report every span a ray spends inside them, which
+// is what constructive solid geometry (see `csg::Csg`) is built from. The
+// spans cover the whole line of the ray, not only `t > 0`, and are sorted
+// by `t` without overlapping.
+pub trait Solid: Hitable {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>>;
+}
@@ -28,10 +28,10 @@ impl Hitable for HitableList {
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
         if self.size() > 0 {
             let first_box = self.hitables[0].bounding_box(t0, t1);
-            if let Some(bbox) = first_box {
+            if let Some(mut bbox) = first_box {
                 for i in 1..self.size() {
                     if let Some(temp_box) = self.hitables[i].bounding_box(t0, t1) {
-                        let bbox = surrounding_box(bbox, temp_box);
+                        bbox = surrounding_box(bbox, temp_box);
                     } else {
                         return None;
                     }
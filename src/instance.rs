@@ -0,0 +1,111 @@
+use crate::aabb::AABB;
+use crate::bvh::FlatBVH;
+use crate::hitable::{HitRecord, Hitable};
+use crate::hitable_list::HitableList;
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::transform::Transform;
+
+use std::sync::Arc;
+
+// One placement of a shared prototype
+pub type Instance = Transform<Arc<dyn Hitable>>;
+
+// Many placements of the same prototype. Each instance only holds its
+// transform and a reference to the geometry, and the instances are kept in a
+// BVH of their own.
+pub struct Instances {
+    pub prototype: Arc<dyn Hitable>,
+    pub bvh: FlatBVH,
+}
+
+impl Instances {
+    pub fn new(
+        prototype: Arc<dyn Hitable>,
+        matrices: Vec<Matrix4>,
+        time0: f32,
+        time1: f32,
+    ) -> Self {
+        let hitables: Vec<Box<dyn Hitable>> = matrices
+            .into_iter()
+            .map(|matrix| -> Box<dyn Hitable> {
+                Box::new(Instance::with_matrix(prototype.clone(), matrix))
+            })
+            .collect();
+        Instances {
+            prototype,
+            bvh: FlatBVH::new(HitableList { hitables }, time0, time1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bvh.hitables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bvh.hitables.is_empty()
+    }
+}
+
+impl Hitable for Instances {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn shared_prototype() {
+        let white = Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5));
+        let prototype: Arc<dyn Hitable> = Arc::new(Block::new(
+            Vec3::new(-0.5, 0.0, -0.5),
+            Vec3::new(0.5, 1.0, 0.5),
+            white.clone(),
+        ));
+        let mut matrices = vec![];
+        let mut list: Vec<Box<dyn Hitable>> = vec![];
+        for i in 0..10 {
+            for j in 0..10 {
+                let offset = Vec3::new(3.0 * i as f32, 0.0, 3.0 * j as f32);
+                let angle = 9.0 * (i + j) as f32;
+                matrices.push(Matrix4::translation(offset) * Matrix4::rotation_y(angle));
+                list.push(Box::new(
+                    Transform::new(Block::new(
+                        Vec3::new(-0.5, 0.0, -0.5),
+                        Vec3::new(0.5, 1.0, 0.5),
+                        white.clone(),
+                    ))
+                    .rotate_y(angle)
+                    .translate(offset),
+                ));
+            }
+        }
+        let instances = Instances::new(prototype.clone(), matrices, 0.0, 1.0);
+        let list = HitableList { hitables: list };
+        assert_eq!(instances.len(), 100);
+        assert_eq!(Arc::strong_count(&prototype), 102);
+
+        for i in 0..200 {
+            let x = 0.15 * i as f32;
+            let r = Ray::new(Vec3::new(x, 5.0, -5.0), Vec3::new(0.01, -0.2, 1.0), 0.0);
+            let expected = list.hit(&r, 0.001, f32::MAX);
+            let actual = instances.hit(&r, 0.001, f32::MAX);
+            match (expected, actual) {
+                (Some(a), Some(b)) => assert!((a.t - b.t).abs() < 1e-3),
+                (None, None) => {}
+                _ => panic!("hit mismatch for ray {}", i),
+            }
+        }
+    }
+}
@@ -1,17 +1,46 @@
 pub mod aabb;
+pub mod animated;
 pub mod block;
+pub mod bvh;
 pub mod camera;
+pub mod cloud;
 pub mod color;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod displacement;
 pub mod flip;
+pub mod heightfield;
 pub mod hitable;
 pub mod hitable_list;
+pub mod instance;
 pub mod material;
+pub mod matrix;
 pub mod medium;
+pub mod mesh;
+pub mod microfacet;
+pub mod obj;
+pub mod patch;
 pub mod perlin;
+pub mod plane;
+pub mod ply;
+pub mod principled;
+pub mod quad;
+pub mod quadric;
+pub mod quaternion;
 pub mod ray;
 pub mod rectangle;
 pub mod scene;
+pub mod sdf;
+pub mod spectrum;
 pub mod sphere;
+pub mod stl;
+pub mod subdivision;
 pub mod texture;
+pub mod thin_film;
+pub mod torus;
+pub mod transform;
 pub mod translate;
+pub mod triangle;
 pub mod vec3;
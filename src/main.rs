@@ -4,27 +4,12 @@ use std::fs;
 use std::io::{BufWriter, Write};
 use std::time::Instant;
 
-mod aabb;
-mod block;
-mod camera;
-mod color;
-mod flip;
-mod hitable;
-mod hitable_list;
-mod material;
-mod medium;
-mod perlin;
-mod ray;
-mod rectangle;
-mod scene;
-mod sphere;
-mod texture;
-mod translate;
-mod vec3;
-
-use color::color;
-use ray::Ray;
-use vec3::Vec3;
+use rust_rtnw::bvh::FlatBVH;
+use rust_rtnw::camera;
+use rust_rtnw::color::color;
+use rust_rtnw::ray::Ray;
+use rust_rtnw::scene;
+use rust_rtnw::vec3::Vec3;
 
 fn main() {
     let nx: u32 = 400;
@@ -32,7 +17,7 @@ fn main() {
     let ns: u32 = 50;
 
     // Objects setup
-    let world = scene::blocks();
+    let world = FlatBVH::new(scene::blocks(), 0.0, 1.0);
 
     // Camera setup
     let cam = camera::camera_for_cornell_box(nx, ny);
@@ -1,5 +1,7 @@
 use crate::hitable::HitRecord;
+use crate::microfacet::{self, Frame, TrowbridgeReitz};
 use crate::ray::Ray;
+use crate::spectrum::{self, Dispersion};
 use crate::texture::*;
 use crate::vec3::Vec3;
 
@@ -33,8 +35,9 @@ impl<T: Texture> Material for Lambertian<T> {
             origin: rec.p,
             direction: target - rec.p,
             time: r_in.time,
+            wavelength: None,
         };
-        let attenuation: Vec3 = self.albedo.value(rec.u, rec.v, &rec.p);
+        let attenuation: Vec3 = self.albedo.value_at(rec);
 
         Some(ScatterRecord {
             attenuation,
@@ -70,6 +73,7 @@ impl Material for Metal {
             origin: rec.p,
             direction: reflected + f * random_in_unit_sphere(),
             time: r_in.time,
+            wavelength: None,
         };
         let attenuation: Vec3 = self.albedo;
 
@@ -88,6 +92,99 @@ impl Material for Metal {
     }
 }
 
+// Rough metal with a GGX distribution of microfacets and the Fresnel
+// reflectance of a complex index of refraction `eta + i k`, per RGB channel.
+// Roughness is read from a texture (the mean of its channels) so that it can
+// vary over the surface.
+#[derive(Clone)]
+pub struct Conductor<T: Texture> {
+    pub eta: Vec3,
+    pub k: Vec3,
+    pub roughness: T,
+    pub anisotropy: f32, // 0 for round highlights, towards 1 for brushed metal
+}
+
+impl<T: Texture> Conductor<T> {
+    pub fn new(eta: Vec3, k: Vec3, roughness: T) -> Self {
+        Conductor {
+            eta,
+            k,
+            roughness,
+            anisotropy: 0.0,
+        }
+    }
+
+    pub fn gold(roughness: T) -> Self {
+        Conductor::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: T) -> Self {
+        Conductor::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: T) -> Self {
+        Conductor::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: T) -> Self {
+        Conductor::new(
+            Vec3::new(0.155, 0.117, 0.138),
+            Vec3::new(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+}
+
+impl<T: Texture> Material for Conductor<T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // thin sheets are metal on both sides
+        let normal: Vec3 = if r_in.direction.dot(&rec.normal) > 0.0 {
+            -rec.normal
+        } else {
+            rec.normal
+        };
+        let frame = Frame::from_normal(normal);
+        let wo: Vec3 = frame.to_local(&-r_in.direction.unit_vector());
+        let rough: Vec3 = self.roughness.value_at(rec);
+        let ggx =
+            TrowbridgeReitz::from_roughness((rough.x + rough.y + rough.z) / 3.0, self.anisotropy);
+
+        // the reflection off a visible microfacet only misses the surface
+        // when it is shadowed, which G2 / G1 already accounts for
+        let h: Vec3 = ggx.sample_visible_normal(&wo);
+        let wi: Vec3 = microfacet::reflect(&wo, &h);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let fresnel: Vec3 = microfacet::fresnel_conductor(wo.dot(&h), &self.eta, &self.k);
+        Some(ScatterRecord {
+            attenuation: fresnel * (ggx.g2(&wo, &wi) / ggx.g1(&wo)),
+            scattered: Ray::new(rec.p, frame.to_world(&wi), r_in.time),
+        })
+    }
+
+    fn emitted(&self, _u: f32, _v: f32, _p: &Vec3) -> Vec3 {
+        Vec3::zeros()
+    }
+}
+
 fn random_in_unit_sphere() -> Vec3 {
     let mut p = Vec3::make_unit_vector();
     while p.squared_length() > 1.0 {
@@ -108,56 +205,177 @@ fn reflect(v: Vec3, n: Vec3) -> Vec3 {
 #[derive(Clone)]
 pub struct Dielectric {
     pub refract_idx: f32,
+    pub absorption: Vec3, // per unit of distance travelled inside, per channel
+    pub dispersion: Option<Dispersion>, // overrides `refract_idx` when set
 }
 
 impl Dielectric {
     pub fn new(refract_idx: f32) -> Self {
-        Dielectric { refract_idx }
+        Dielectric {
+            refract_idx,
+            absorption: Vec3::zeros(),
+            dispersion: None,
+        }
+    }
+
+    // Glass splitting white light into a rainbow. The first dispersive hit
+    // picks a single wavelength for the rest of the path, weighted to give
+    // back the right color on average.
+    pub fn dispersive(dispersion: Dispersion) -> Self {
+        Dielectric {
+            dispersion: Some(dispersion),
+            ..Dielectric::new(dispersion.index(587.6))
+        }
+    }
+
+    pub fn with_absorption(mut self, absorption: Vec3) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Tinted glass that lets `color` through after `distance` inside
+    pub fn with_color(self, color: Vec3, distance: f32) -> Self {
+        self.with_absorption(absorption_for(color, distance))
     }
 }
 
+fn absorption_for(color: Vec3, distance: f32) -> Vec3 {
+    let sigma = |c: f32| -c.max(1e-6).ln() / distance;
+    Vec3::new(sigma(color.x), sigma(color.y), sigma(color.z))
+}
+
+// Beer-Lambert: a ray leaving the object has travelled in a straight line
+// from where it entered or last bounced inside, which is where it started
+fn transmittance(absorption: &Vec3, r_in: &Ray, rec: &HitRecord) -> Vec3 {
+    let distance: f32 = rec.t * r_in.direction.length();
+    Vec3::new(
+        (-absorption.x * distance).exp(),
+        (-absorption.y * distance).exp(),
+        (-absorption.z * distance).exp(),
+    )
+}
+
 impl Material for Dielectric {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let reflected: Vec3 = reflect(r_in.direction, rec.normal);
-        let attenuation = Vec3::new(1.0, 1.0, 1.0);
+        let mut attenuation: Vec3 = if r_in.direction.dot(&rec.normal) > 0.0 {
+            transmittance(&self.absorption, r_in, rec)
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        };
+
+        let mut refract_idx: f32 = self.refract_idx;
+        let mut wavelength: Option<f32> = r_in.wavelength;
+        if let Some(dispersion) = &self.dispersion {
+            let w: f32 = wavelength.unwrap_or_else(|| {
+                let w: f32 = spectrum::sample_wavelength();
+                attenuation = attenuation * spectrum::wavelength_to_rgb(w);
+                w
+            });
+            refract_idx = dispersion.index(w);
+            wavelength = Some(w);
+        }
 
         let outward_normal: Vec3;
         let ni_over_nt: f32;
         let cosine: f32;
-        let reflect_prob: f32;
 
         if r_in.direction.dot(&rec.normal) > 0.0 {
             outward_normal = -rec.normal;
-            ni_over_nt = self.refract_idx;
-            cosine = self.refract_idx * r_in.direction.dot(&rec.normal) / r_in.direction.length();
+            ni_over_nt = refract_idx;
+            cosine = refract_idx * r_in.direction.dot(&rec.normal) / r_in.direction.length();
         } else {
             outward_normal = rec.normal;
-            ni_over_nt = 1.0 / self.refract_idx;
+            ni_over_nt = 1.0 / refract_idx;
             cosine = -r_in.direction.dot(&rec.normal) / r_in.direction.length();
         }
 
-        match refract(&r_in.direction, &outward_normal, ni_over_nt) {
-            Some(refracted) => {
-                reflect_prob = schlick(cosine, self.refract_idx);
-                if rand::random::<f32>() > reflect_prob {
-                    Some(ScatterRecord {
-                        attenuation,
-                        scattered: Ray::new(rec.p, refracted, r_in.time),
-                    })
-                } else {
-                    Some(ScatterRecord {
-                        attenuation,
-                        scattered: Ray::new(rec.p, reflected, r_in.time),
-                    })
-                }
-            }
-            None => Some(ScatterRecord {
-                attenuation,
-                scattered: Ray::new(rec.p, reflected, r_in.time),
-            }),
+        let direction: Vec3 = match refract(&r_in.direction, &outward_normal, ni_over_nt) {
+            Some(refracted) if rand::random::<f32>() > schlick(cosine, refract_idx) => refracted,
+            _ => reflected,
+        };
+        Some(ScatterRecord {
+            attenuation,
+            scattered: Ray {
+                wavelength,
+                ..Ray::new(rec.p, direction, r_in.time)
+            },
+        })
+    }
+
+    fn emitted(&self, _u: f32, _v: f32, _p: &Vec3) -> Vec3 {
+        Vec3::zeros()
+    }
+}
+
+// Frosted glass: a GGX distribution of microfacets each refracting or
+// reflecting like `Dielectric`, with the same absorption inside
+#[derive(Clone)]
+pub struct RoughDielectric<T: Texture> {
+    pub refract_idx: f32,
+    pub roughness: T,
+    pub absorption: Vec3,
+}
+
+impl<T: Texture> RoughDielectric<T> {
+    pub fn new(refract_idx: f32, roughness: T) -> Self {
+        RoughDielectric {
+            refract_idx,
+            roughness,
+            absorption: Vec3::zeros(),
         }
     }
 
+    pub fn with_absorption(mut self, absorption: Vec3) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    pub fn with_color(self, color: Vec3, distance: f32) -> Self {
+        self.with_absorption(absorption_for(color, distance))
+    }
+}
+
+impl<T: Texture> Material for RoughDielectric<T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let inside: bool = r_in.direction.dot(&rec.normal) > 0.0;
+        let (normal, eta, attenuation) = if inside {
+            (
+                -rec.normal,
+                1.0 / self.refract_idx,
+                transmittance(&self.absorption, r_in, rec),
+            )
+        } else {
+            (rec.normal, self.refract_idx, Vec3::new(1.0, 1.0, 1.0))
+        };
+        let frame = Frame::from_normal(normal);
+        let wo: Vec3 = frame.to_local(&-r_in.direction.unit_vector());
+        let rough: Vec3 = self.roughness.value_at(rec);
+        let ggx = TrowbridgeReitz::from_roughness((rough.x + rough.y + rough.z) / 3.0, 0.0);
+
+        // reflect or refract at a visible microfacet in proportion to the
+        // Fresnel term, so that G2 / G1 is the only weight left
+        let h: Vec3 = ggx.sample_visible_normal(&wo);
+        let fresnel: f32 = microfacet::fresnel_dielectric(wo.dot(&h), eta);
+        let wi: Vec3 = if rand::random::<f32>() < fresnel {
+            let wi: Vec3 = microfacet::reflect(&wo, &h);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            wi
+        } else {
+            let wi: Vec3 = microfacet::refract(&wo, &h, eta)?;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            wi
+        };
+        Some(ScatterRecord {
+            attenuation: attenuation * ggx.g2(&wo, &wi) / ggx.g1(&wo),
+            scattered: Ray::new(rec.p, frame.to_world(&wi), r_in.time),
+        })
+    }
+
     fn emitted(&self, _u: f32, _v: f32, _p: &Vec3) -> Vec3 {
         Vec3::zeros()
     }
@@ -216,7 +434,7 @@ impl<T: Texture> Isotropic<T> {
 impl<T: Texture> Material for Isotropic<T> {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
         let scattered = Ray::new(rec.p, random_in_unit_sphere(), r_in.time);
-        let attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        let attenuation = self.albedo.value_at(rec);
         Some(ScatterRecord {
             scattered,
             attenuation,
@@ -227,3 +445,229 @@ impl<T: Texture> Material for Isotropic<T> {
         Vec3::zeros()
     }
 }
+
+// Either of two materials, `second` with a probability given by the mean of
+// the mask's channels, so a black and white texture paints one over the other
+#[derive(Clone)]
+pub struct MixMaterial<A: Material, B: Material, T: Texture> {
+    pub first: A,
+    pub second: B,
+    pub mask: T,
+}
+
+impl<A: Material, B: Material, T: Texture> MixMaterial<A, B, T> {
+    pub fn new(first: A, second: B, mask: T) -> Self {
+        MixMaterial {
+            first,
+            second,
+            mask,
+        }
+    }
+}
+
+fn mix_weight(mask: Vec3) -> f32 {
+    ((mask.x + mask.y + mask.z) / 3.0).clamp(0.0, 1.0)
+}
+
+impl<A: Material, B: Material, T: Texture> Material for MixMaterial<A, B, T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        if rand::random::<f32>() < mix_weight(self.mask.value_at(rec)) {
+            self.second.scatter(r_in, rec)
+        } else {
+            self.first.scatter(r_in, rec)
+        }
+    }
+
+    // Without the hit record, textures overriding `value_at` (like
+    // `VertexColorTexture`) fall back to `value` here, and may mask the
+    // emission differently from the scattering
+    fn emitted(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        let weight: f32 = mix_weight(self.mask.value(u, v, p));
+        (1.0 - weight) * self.first.emitted(u, v, p) + weight * self.second.emitted(u, v, p)
+    }
+}
+
+// A smooth clear layer, like varnish or the clearcoat of car paint, over
+// another material. Light is either reflected off the top following Fresnel,
+// or reaches the base and is let back out following Fresnel again, tinted by
+// `absorption` along the way. The coat is thin, so the base scatters as if
+// it were in the open and light bouncing between the two is left out.
+#[derive(Clone)]
+pub struct Coated<M: Material> {
+    pub base: M,
+    pub refract_idx: f32,
+    pub absorption: Vec3, // per crossing of the coat head on, per channel
+}
+
+impl<M: Material> Coated<M> {
+    pub fn new(base: M, refract_idx: f32) -> Self {
+        Coated {
+            base,
+            refract_idx,
+            absorption: Vec3::zeros(),
+        }
+    }
+
+    pub fn with_absorption(mut self, absorption: Vec3) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Coat that tints light going straight down to the base and back up
+    // by `color`
+    pub fn with_color(self, color: Vec3) -> Self {
+        self.with_absorption(absorption_for(color, 2.0))
+    }
+}
+
+impl<M: Material> Material for Coated<M> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        // the coat is only on the outside
+        if r_in.direction.dot(&rec.normal) > 0.0 {
+            return self.base.scatter(r_in, rec);
+        }
+        let unit_direction: Vec3 = r_in.direction.unit_vector();
+        let cos_in: f32 = -unit_direction.dot(&rec.normal);
+        if rand::random::<f32>() < microfacet::fresnel_dielectric(cos_in, self.refract_idx) {
+            return Some(ScatterRecord {
+                attenuation: Vec3::new(1.0, 1.0, 1.0),
+                scattered: Ray::new(rec.p, reflect(unit_direction, rec.normal), r_in.time),
+            });
+        }
+
+        let scatter: ScatterRecord = self.base.scatter(r_in, rec)?;
+        let cos_out: f32 = scatter.scattered.direction.unit_vector().dot(&rec.normal);
+        if cos_out <= 0.0 {
+            // transmitted through the base, the coat doesn't matter
+            return Some(scatter);
+        }
+        let fresnel: f32 = microfacet::fresnel_dielectric(cos_out, self.refract_idx);
+
+        // path lengths through the coat follow the directions bent inside
+        let inside = |cos: f32| (1.0 - (1.0 - cos * cos) / self.refract_idx.powi(2)).sqrt();
+        let depth: Vec3 =
+            self.absorption * (1.0 / inside(cos_in).max(1e-4) + 1.0 / inside(cos_out).max(1e-4));
+        let tint = Vec3::new((-depth.x).exp(), (-depth.y).exp(), (-depth.z).exp());
+        Some(ScatterRecord {
+            attenuation: (1.0 - fresnel) * scatter.attenuation * tint,
+            ..scatter
+        })
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        self.base.emitted(u, v, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable::Hitable;
+    use crate::sphere::Sphere;
+
+    #[test]
+    fn tinted_glass() {
+        // straight through the middle of a ball of glass 2 across: the ray
+        // leaving it has crossed 2 units of the absorbing inside
+        let color = Vec3::new(0.8, 0.5, 0.2);
+        let glass = Dielectric::new(1.5).with_color(color, 1.0);
+        let ball = Sphere::new(Vec3::zeros(), 1.0, glass.clone());
+        let inside = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -2.0), 0.0);
+        let rec = ball.hit(&inside, 0.001, f32::MAX).unwrap();
+        let expected = Vec3::new(0.64, 0.25, 0.04);
+        for _ in 0..20 {
+            let scatter = glass.scatter(&inside, &rec).unwrap();
+            assert!((scatter.attenuation - expected).length() < 1e-4);
+        }
+
+        // nearly smooth rough glass bends the ray like the smooth one
+        let frosted = RoughDielectric::new(1.5, ConstantTexture::new(0.0, 0.0, 0.0));
+        let r = Ray::new(Vec3::new(-1.2, 1.6, 0.0), Vec3::new(0.6, -0.8, 0.0), 0.0);
+        let ball = Sphere::new(Vec3::new(0.0, -1.0, 0.0), 1.0, frosted.clone());
+        let rec = ball.hit(&r, 0.001, f32::MAX).unwrap();
+        let mut refracted = 0;
+        let mut mean = Vec3::zeros();
+        for _ in 0..200 {
+            let scatter = frosted.scatter(&r, &rec).unwrap();
+            let d: Vec3 = scatter.scattered.direction.unit_vector();
+            if d.y < 0.0 {
+                refracted += 1;
+                mean += d;
+            }
+        }
+        let mean: Vec3 = mean.unit_vector();
+        assert!((mean - Vec3::new(0.4, -(0.84f32).sqrt(), 0.0)).length() < 0.01);
+        assert!(refracted > 150);
+    }
+
+    #[test]
+    fn dispersion() {
+        let prism = Dielectric::dispersive(Dispersion::diamond());
+        let ball = Sphere::new(Vec3::new(0.0, -1.0, 0.0), 1.0, prism.clone());
+        let r = Ray::new(Vec3::new(-1.2, 1.6, 0.0), Vec3::new(0.6, -0.8, 0.0), 0.0);
+        let rec = ball.hit(&r, 0.001, f32::MAX).unwrap();
+        // bent towards the normal (-y) by how much each wavelength is slowed
+        let bend = |wavelength: f32| {
+            let r = Ray {
+                wavelength: Some(wavelength),
+                ..r
+            };
+            loop {
+                let scatter = prism.scatter(&r, &rec).unwrap();
+                assert_eq!(scatter.scattered.wavelength, Some(wavelength));
+                assert_eq!(scatter.attenuation, Vec3::new(1.0, 1.0, 1.0));
+                let d: Vec3 = scatter.scattered.direction.unit_vector();
+                if d.y < 0.0 {
+                    return d.x;
+                }
+            }
+        };
+        assert!(bend(450.0) < bend(650.0) - 0.003);
+
+        // white light gets a wavelength, and its color as the weight
+        let scatter = prism.scatter(&r, &rec).unwrap();
+        let wavelength: f32 = scatter.scattered.wavelength.unwrap();
+        assert_eq!(scatter.attenuation, spectrum::wavelength_to_rgb(wavelength));
+    }
+
+    #[test]
+    fn mix_and_coat() {
+        let sphere = Sphere::new(
+            Vec3::zeros(),
+            1.0,
+            Lambertian::new(ConstantTexture::new(1.0, 1.0, 1.0)),
+        );
+        let r = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = sphere.hit(&r, 0.001, f32::MAX).unwrap();
+        let mean = |material: &dyn Material| {
+            let n = 20000;
+            let mut sum = Vec3::zeros();
+            for _ in 0..n {
+                if let Some(scatter) = material.scatter(&r, &rec) {
+                    sum += scatter.attenuation;
+                }
+            }
+            sum / n as f32
+        };
+
+        // a quarter of white over black
+        let white = Lambertian::new(ConstantTexture::new(1.0, 1.0, 1.0));
+        let black = Lambertian::new(ConstantTexture::new(0.0, 0.0, 0.0));
+        let mix = MixMaterial::new(
+            black.clone(),
+            white.clone(),
+            ConstantTexture::new(0.25, 0.25, 0.25),
+        );
+        assert!((mean(&mix).x - 0.25).abs() < 0.02);
+
+        // varnish over black only shows its 4% reflection head on, and
+        // over white loses a little light on the way out
+        let varnished = Coated::new(black, 1.5);
+        assert!((mean(&varnished).x - 0.04).abs() < 0.005);
+        let varnished = Coated::new(white, 1.5);
+        let albedo: f32 = mean(&varnished).x;
+        assert!(albedo > 0.85 && albedo < 0.97, "{}", albedo);
+        let tinted: Vec3 = mean(&varnished.with_color(Vec3::new(1.0, 0.5, 1.0)));
+        assert!(tinted.y < 0.6 * tinted.x && (tinted.x - tinted.z).abs() < 0.03);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::vec3::Vec3;
+
+use std::ops::Mul;
+
+// Row-major 4x4 matrix for affine transforms of points (w = 1) and
+// vectors (w = 0)
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Self {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(factor: Vec3) -> Self {
+        Matrix4::new([
+            [factor.x, 0.0, 0.0, 0.0],
+            [0.0, factor.y, 0.0, 0.0],
+            [0.0, 0.0, factor.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Rotation of `angle` degrees around `axis`, counter-clockwise when the
+    // axis points towards the viewer
+    pub fn rotation(axis: Vec3, angle: f32) -> Self {
+        let a: Vec3 = axis.unit_vector();
+        let (s, c) = angle.to_radians().sin_cos();
+        let t: f32 = 1.0 - c;
+        Matrix4::new([
+            [
+                c + a.x * a.x * t,
+                a.x * a.y * t - a.z * s,
+                a.x * a.z * t + a.y * s,
+                0.0,
+            ],
+            [
+                a.y * a.x * t + a.z * s,
+                c + a.y * a.y * t,
+                a.y * a.z * t - a.x * s,
+                0.0,
+            ],
+            [
+                a.z * a.x * t - a.y * s,
+                a.z * a.y * t + a.x * s,
+                c + a.z * a.z * t,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_x(angle: f32) -> Self {
+        Matrix4::rotation(Vec3::new(1.0, 0.0, 0.0), angle)
+    }
+
+    pub fn rotation_y(angle: f32) -> Self {
+        Matrix4::rotation(Vec3::new(0.0, 1.0, 0.0), angle)
+    }
+
+    pub fn rotation_z(angle: f32) -> Self {
+        Matrix4::rotation(Vec3::new(0.0, 0.0, 1.0), angle)
+    }
+
+    // x' = x + xy * y + xz * z, and so on for y' and z'
+    pub fn shear(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        Matrix4::new([
+            [1.0, xy, xz, 0.0],
+            [yx, 1.0, yz, 0.0],
+            [zx, zy, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = self.m[j][i];
+            }
+        }
+        Matrix4::new(m)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting. `None` if the matrix
+    // is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap())
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let d: f32 = a[col][col];
+            for j in 0..4 {
+                a[col][j] /= d;
+                inv[col][j] /= d;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let f: f32 = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= f * a[col][j];
+                        inv[row][j] -= f * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4::new(inv))
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        let x: f32 = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y: f32 = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z: f32 = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w: f32 = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4::new(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotation() {
+        let p = Vec3::new(1.0, 0.0, 0.0);
+        assert_near(
+            Matrix4::rotation_z(90.0).transform_point(&p),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert_near(
+            Matrix4::rotation_y(90.0).transform_point(&p),
+            Vec3::new(0.0, 0.0, -1.0),
+        );
+        let axis = Vec3::new(1.0, 1.0, 1.0);
+        assert_near(
+            Matrix4::rotation(axis, 120.0).transform_point(&p),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Matrix4::translation(Vec3::new(1.0, -2.0, 3.0))
+            * Matrix4::rotation(Vec3::new(1.0, 2.0, 3.0), 37.0)
+            * Matrix4::scaling(Vec3::new(2.0, 0.5, 3.0))
+            * Matrix4::shear(0.3, 0.0, 0.0, 0.1, 0.2, 0.0);
+        let inv = m.inverse().unwrap();
+        let p = Vec3::new(0.3, -4.0, 2.5);
+        assert_near(inv.transform_point(&m.transform_point(&p)), p);
+        assert_near(m.transform_vector(&inv.transform_vector(&p)), p);
+
+        let id = m * inv;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((id.m[i][j] - expected).abs() < 1e-5);
+            }
+        }
+        assert!(Matrix4::scaling(Vec3::new(1.0, 0.0, 1.0))
+            .inverse()
+            .is_none());
+    }
+}
@@ -48,6 +48,7 @@ impl<H: Hitable, T: Texture> Hitable for ConstantMedium<H, T> {
                             v: 0.0,
                             p: r.point_at_parameter(t),
                             normal: Vec3::new(1.0, 0.0, 0.0),
+                            color: None,
                             material: &self.phase_function,
                         });
                     }
@@ -0,0 +1,358 @@
+use crate::aabb::AABB;
+use crate::bvh::BVHTree;
+use crate::hitable::{HitRecord, Hitable, Interval, Solid};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle::*;
+use crate::vec3::Vec3;
+
+use image;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// Indexed triangle mesh. Vertex attributes are stored once and shared by the
+// faces referring to them, and the faces are kept in a BVH of their own.
+pub struct TriangleMesh<M: Material> {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,     // empty, or one per position
+    pub uvs: Vec<(f32, f32)>,   // empty, or one per position
+    pub colors: Vec<Vec3>,      // empty, or one per position
+    pub indices: Vec<[u32; 3]>, // one entry per face
+    pub material: M,
+    pub tree: BVHTree,
+}
+
+impl<M: Material> TriangleMesh<M> {
+    pub fn new(positions: Vec<Vec3>, indices: Vec<[u32; 3]>, material: M) -> Self {
+        let bboxes: Vec<AABB> = indices
+            .iter()
+            .map(|face| {
+                triangle_box(
+                    &positions[face[0] as usize],
+                    &positions[face[1] as usize],
+                    &positions[face[2] as usize],
+                )
+            })
+            .collect();
+        let tree = BVHTree::new(&bboxes);
+        TriangleMesh {
+            positions,
+            normals: vec![],
+            uvs: vec![],
+            colors: vec![],
+            indices,
+            material,
+            tree,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Vec3>) -> Self {
+        assert_eq!(normals.len(), self.positions.len());
+        self.normals = normals;
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<(f32, f32)>) -> Self {
+        assert_eq!(uvs.len(), self.positions.len());
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn with_colors(mut self, colors: Vec<Vec3>) -> Self {
+        assert_eq!(colors.len(), self.positions.len());
+        self.colors = colors;
+        self
+    }
+
+    // Smooth vertex normals, see `vertex_normals`
+    pub fn compute_normals(&mut self) {
+        self.normals = vertex_normals(&self.positions, &self.indices);
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn face_positions(&self, face: &[u32; 3]) -> [Vec3; 3] {
+        [
+            self.positions[face[0] as usize],
+            self.positions[face[1] as usize],
+            self.positions[face[2] as usize],
+        ]
+    }
+
+    fn hit_face(&self, face_idx: usize, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let face = &self.indices[face_idx];
+        let [v0, v1, v2] = self.face_positions(face);
+        let (t, b1, b2) = intersect_triangle(r, &v0, &v1, &v2, t_min, t_max)?;
+        let [i0, i1, i2] = [face[0] as usize, face[1] as usize, face[2] as usize];
+        let mut normal: Vec3 = if self.normals.is_empty() {
+            Vec3::zeros()
+        } else {
+            interpolate(
+                &self.normals[i0],
+                &self.normals[i1],
+                &self.normals[i2],
+                b1,
+                b2,
+            )
+        };
+        if normal.squared_length() == 0.0 {
+            normal = (v1 - v0).cross(&(v2 - v0));
+        }
+        let (u, v) = if self.uvs.is_empty() {
+            (b1, b2)
+        } else {
+            interpolate_uv(&[self.uvs[i0], self.uvs[i1], self.uvs[i2]], b1, b2)
+        };
+        let color = if self.colors.is_empty() {
+            None
+        } else {
+            Some(interpolate(
+                &self.colors[i0],
+                &self.colors[i1],
+                &self.colors[i2],
+                b1,
+                b2,
+            ))
+        };
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p: r.point_at_parameter(t),
+            normal: normal.unit_vector(),
+            color,
+            material: &self.material,
+        })
+    }
+}
+
+impl<M: Material> Hitable for TriangleMesh<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.tree.hit(r, t_min, t_max, |face_idx, closest_so_far| {
+            self.hit_face(face_idx, r, t_min, closest_so_far)
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        self.tree.bounding_box()
+    }
+}
+
+impl<M: Material> Solid for TriangleMesh<M> {
+    // Crossings are paired up by which side of each triangle the ray comes
+    // from, so the mesh has to be closed and wound counter-clockwise seen
+    // from outside
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        let mut hits: Vec<(bool, HitRecord)> = Vec::new();
+        self.tree.hit(r, -f32::MAX, f32::MAX, |face_idx, _| {
+            if let Some(rec) = self.hit_face(face_idx, r, -f32::MAX, f32::MAX) {
+                let [v0, v1, v2] = self.face_positions(&self.indices[face_idx]);
+                let entering = (v1 - v0).cross(&(v2 - v0)).dot(&r.direction) < 0.0;
+                hits.push((entering, rec));
+            }
+            // keep looking, every crossing is needed
+            None
+        });
+        hits.sort_by(|a, b| a.1.t.total_cmp(&b.1.t));
+
+        let mut intervals = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        for (entering, rec) in hits {
+            if entering {
+                enter.get_or_insert(rec);
+            } else if let Some(enter) = enter.take() {
+                intervals.push(Interval { enter, exit: rec });
+            }
+        }
+        intervals
+    }
+}
+
+// Smooth vertex normals, averaging the normals of the adjacent faces
+// weighted by their area
+pub fn vertex_normals(positions: &[Vec3], indices: &[[u32; 3]]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::zeros(); positions.len()];
+    for face in indices {
+        let [v0, v1, v2] = face.map(|i| positions[i as usize]);
+        let n: Vec3 = (v1 - v0).cross(&(v2 - v0));
+        for &i in face {
+            normals[i as usize] += n;
+        }
+    }
+    for n in &mut normals {
+        if n.squared_length() > 0.0 {
+            *n = n.unit_vector();
+        }
+    }
+    normals
+}
+
+// Points at which vertices are found, and the point of each vertex. Loaders
+// split vertices wherever the uvs or normals change; welding them back lets
+// seams move as one. Adding zero turns -0 into 0 so that both weld.
+pub fn weld(positions: &[Vec3]) -> (Vec<Vec3>, Vec<u32>) {
+    let mut ids: HashMap<[u32; 3], u32> = HashMap::new();
+    let mut points: Vec<Vec3> = vec![];
+    let point_of: Vec<u32> = positions
+        .iter()
+        .map(|p| {
+            let key = [
+                (p.x + 0.0).to_bits(),
+                (p.y + 0.0).to_bits(),
+                (p.z + 0.0).to_bits(),
+            ];
+            *ids.entry(key).or_insert_with(|| {
+                points.push(*p);
+                points.len() as u32 - 1
+            })
+        })
+        .collect();
+    (points, point_of)
+}
+
+// Vertex and index buffers as read by the mesh loaders, before they are
+// turned into a `TriangleMesh`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub colors: Vec<Vec3>,
+    pub indices: Vec<[u32; 3]>,
+    // the faces before triangulation, when the file had polygons; empty for
+    // formats that only store triangles
+    pub polygons: Vec<Vec<u32>>,
+}
+
+impl MeshData {
+    // Faces as vertex loops, polygons if there are any
+    pub fn faces(&self) -> Vec<Vec<u32>> {
+        if self.polygons.is_empty() {
+            self.indices.iter().map(|face| face.to_vec()).collect()
+        } else {
+            self.polygons.clone()
+        }
+    }
+
+    pub fn into_mesh<M: Material>(self, material: M) -> TriangleMesh<M> {
+        let mut mesh = TriangleMesh::new(self.positions, self.indices, material);
+        if !self.normals.is_empty() {
+            mesh = mesh.with_normals(self.normals);
+        }
+        if !self.uvs.is_empty() {
+            mesh = mesh.with_uvs(self.uvs);
+        }
+        if !self.colors.is_empty() {
+            mesh = mesh.with_colors(self.colors);
+        }
+        mesh
+    }
+}
+
+// Anything that can go wrong loading a mesh file, whatever its format
+#[derive(Debug)]
+pub enum MeshError {
+    Io(PathBuf, io::Error),
+    Image(PathBuf, image::ImageError),
+    Parse(String),
+    MissingMaterial(String),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            MeshError::Image(path, e) => write!(f, "{}: {}", path.display(), e),
+            MeshError::Parse(message) => write!(f, "{}", message),
+            MeshError::MissingMaterial(name) => write!(f, "material `{}` is not defined", name),
+        }
+    }
+}
+
+impl Error for MeshError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MeshError::Io(_, e) => Some(e),
+            MeshError::Image(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hitable_list::HitableList;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    use rand::prelude::*;
+
+    fn random_vec(rng: &mut StdRng, range: f32) -> Vec3 {
+        Vec3::new(
+            rng.gen_range(-range, range),
+            rng.gen_range(-range, range),
+            rng.gen_range(-range, range),
+        )
+    }
+
+    #[test]
+    fn same_hits_as_triangles() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let white = Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5));
+        let mut positions = vec![];
+        let mut indices = vec![];
+        let mut hitables: Vec<Box<dyn Hitable>> = vec![];
+        for i in 0..500 {
+            let center = random_vec(&mut rng, 10.0);
+            let v = [
+                center + random_vec(&mut rng, 1.0),
+                center + random_vec(&mut rng, 1.0),
+                center + random_vec(&mut rng, 1.0),
+            ];
+            positions.extend_from_slice(&v);
+            indices.push([3 * i, 3 * i + 1, 3 * i + 2]);
+            hitables.push(Box::new(Triangle::new(v[0], v[1], v[2], white.clone())));
+        }
+        let mesh = TriangleMesh::new(positions, indices, white.clone());
+        let list = HitableList { hitables };
+
+        for _ in 0..2000 {
+            let origin = random_vec(&mut rng, 15.0);
+            let r = Ray::new(origin, random_vec(&mut rng, 10.0) - origin, 0.0);
+            let expected = list.hit(&r, 0.001, f32::MAX);
+            let actual = mesh.hit(&r, 0.001, f32::MAX);
+            assert_eq!(
+                expected.map(|rec| (rec.t, rec.u, rec.v)),
+                actual.map(|rec| (rec.t, rec.u, rec.v))
+            );
+        }
+    }
+
+    #[test]
+    fn smooth_normals() {
+        // two triangles folded along the x axis
+        let positions = vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let indices = vec![[0, 1, 2], [0, 3, 1]];
+        let mut mesh = TriangleMesh::new(
+            positions,
+            indices,
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        );
+        mesh.compute_normals();
+        assert_eq!(mesh.normals[2], Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.normals[3], Vec3::new(0.0, 1.0, 0.0));
+        let n = mesh.normals[1];
+        assert!((n - Vec3::new(0.0, 1.0, 1.0).unit_vector()).length() < 1e-6);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::vec3::Vec3;
+
+use std::f32::consts::PI;
+
+// Orthonormal basis around a shading normal, so that the microfacet
+// functions can work with the normal along +z. The tangent has no relation
+// to the surface parametrization, it only orients anisotropic roughness.
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    pub normal: Vec3,
+}
+
+impl Frame {
+    pub fn from_normal(normal: Vec3) -> Self {
+        let helper = if normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent: Vec3 = (helper - helper.dot(&normal) * normal).unit_vector();
+        Frame {
+            tangent,
+            bitangent: normal.cross(&tangent),
+            normal,
+        }
+    }
+
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            v.dot(&self.tangent),
+            v.dot(&self.bitangent),
+            v.dot(&self.normal),
+        )
+    }
+
+    pub fn to_world(&self, v: &Vec3) -> Vec3 {
+        v.x * self.tangent + v.y * self.bitangent + v.z * self.normal
+    }
+}
+
+// GGX / Trowbridge-Reitz distribution of microfacet normals with the Smith
+// masking function. All directions are in the local frame, pointing away
+// from the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrowbridgeReitz {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Self {
+        // a perfect mirror would make the distribution a delta
+        TrowbridgeReitz {
+            alpha_x: alpha_x.max(1e-3),
+            alpha_y: alpha_y.max(1e-3),
+        }
+    }
+
+    // From a perceptual roughness in [0, 1], squared to get alpha, and an
+    // anisotropy in [0, 1) stretching the highlight along the tangent
+    pub fn from_roughness(roughness: f32, anisotropy: f32) -> Self {
+        let alpha: f32 = roughness * roughness;
+        let aspect: f32 = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        TrowbridgeReitz::new(alpha / aspect, alpha * aspect)
+    }
+
+    pub fn d(&self, h: &Vec3) -> f32 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let x: f32 = h.x / self.alpha_x;
+        let y: f32 = h.y / self.alpha_y;
+        let e: f32 = x * x + y * y + h.z * h.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    pub fn lambda(&self, w: &Vec3) -> f32 {
+        if w.z == 0.0 {
+            return f32::MAX;
+        }
+        let x: f32 = w.x * self.alpha_x;
+        let y: f32 = w.y * self.alpha_y;
+        let alpha2_tan2: f32 = (x * x + y * y) / (w.z * w.z);
+        0.5 * ((1.0 + alpha2_tan2).sqrt() - 1.0)
+    }
+
+    pub fn g1(&self, w: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // Masking and shadowing together (height-correlated)
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Microfacet normal seen from `wo`, with density D(h) G1(wo) |wo.h| / wo.z
+    // (Heitz, "Sampling the GGX distribution of visible normals", 2018).
+    // Reflecting about it and weighting by G2 / G1 keeps every sample's
+    // weight below one.
+    pub fn sample_visible_normal(&self, wo: &Vec3) -> Vec3 {
+        let v: Vec3 = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit_vector();
+        let len2: f32 = v.x * v.x + v.y * v.y;
+        let t1: Vec3 = if len2 > 0.0 {
+            Vec3::new(-v.y, v.x, 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2: Vec3 = v.cross(&t1);
+
+        let r: f32 = rand::random::<f32>().sqrt();
+        let phi: f32 = 2.0 * PI * rand::random::<f32>();
+        let p1: f32 = r * phi.cos();
+        let s: f32 = 0.5 * (1.0 + v.z);
+        let p2: f32 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let n: Vec3 = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v;
+        Vec3::new(self.alpha_x * n.x, self.alpha_y * n.y, n.z.max(1e-6)).unit_vector()
+    }
+}
+
+pub fn reflect(wo: &Vec3, h: &Vec3) -> Vec3 {
+    2.0 * wo.dot(h) * *h - *wo
+}
+
+// Direction refracted through a microfacet `h` into a medium `eta` times
+// denser, None on total internal reflection
+pub fn refract(wo: &Vec3, h: &Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i: f32 = wo.dot(h);
+    let sin2_t: f32 = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t: f32 = (1.0 - sin2_t).sqrt();
+    Some(-*wo / eta + (cos_i / eta - cos_t) * *h)
+}
+
+// Unpolarized Fresnel reflectance of an interface into a medium `eta`
+// times denser, 1 on total internal reflection
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i: f32 = cos_i.clamp(0.0, 1.0);
+    let sin2_t: f32 = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t: f32 = (1.0 - sin2_t).sqrt();
+    let rs: f32 = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp: f32 = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// Unpolarized Fresnel reflectance of a conductor with complex index of
+// refraction `eta + i k`, per channel
+pub fn fresnel_conductor(cos_theta: f32, eta: &Vec3, k: &Vec3) -> Vec3 {
+    let cos2: f32 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2: f32 = 1.0 - cos2;
+    let mut f = Vec3::zeros();
+    for c in 0..3 {
+        let (n, k) = (eta[c], k[c]);
+        let t0: f32 = n * n - k * k - sin2;
+        let a2b2: f32 = (t0 * t0 + 4.0 * n * n * k * k).sqrt();
+        let a: f32 = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+        let t1: f32 = a2b2 + cos2;
+        let t2: f32 = 2.0 * cos_theta * a;
+        let rs: f32 = (t1 - t2) / (t1 + t2);
+        let t3: f32 = cos2 * a2b2 + sin2 * sin2;
+        let t4: f32 = t2 * sin2;
+        let rp: f32 = rs * (t3 - t4) / (t3 + t4);
+        f[c] = 0.5 * (rp + rs);
+    }
+    f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresnel() {
+        let eta = Vec3::new(0.2, 0.9, 1.1);
+        let k = Vec3::new(3.9, 2.5, 2.1);
+        let f = fresnel_conductor(1.0, &eta, &k);
+        for c in 0..3 {
+            let (n, k) = (eta[c], k[c]);
+            let expected: f32 = ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+            assert!((f[c] - expected).abs() < 1e-4);
+        }
+        // everything is reflected at grazing angles
+        let f = fresnel_conductor(0.0, &eta, &k);
+        assert!((f - Vec3::new(1.0, 1.0, 1.0)).length() < 1e-4);
+
+        // 4% off glass head on, and Snell's law through a flat facet
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-5);
+        assert_eq!(fresnel_dielectric(0.5, 1.0 / 1.5), 1.0);
+        let h = Vec3::new(0.0, 0.0, 1.0);
+        let wo = Vec3::new(0.6, 0.0, 0.8);
+        let wi: Vec3 = refract(&wo, &h, 1.5).unwrap();
+        assert!((wi.length() - 1.0).abs() < 1e-5);
+        assert!((wi.x + 0.4).abs() < 1e-5 && wi.z < 0.0);
+        assert!(refract(&Vec3::new(0.8, 0.0, 0.6), &h, 1.0 / 1.5).is_none());
+    }
+
+    // Directional albedo by quadrature over the hemisphere
+    fn albedo(ggx: &TrowbridgeReitz, wo: &Vec3) -> f32 {
+        let n = 200;
+        let mut sum: f32 = 0.0;
+        for i in 0..n {
+            for j in 0..4 * n {
+                let cos_theta: f32 = (i as f32 + 0.5) / n as f32;
+                let phi: f32 = 2.0 * PI * (j as f32 + 0.5) / (4 * n) as f32;
+                let sin_theta: f32 = (1.0 - cos_theta * cos_theta).sqrt();
+                let wi = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                let h: Vec3 = (wi + *wo).unit_vector();
+                let brdf: f32 = ggx.d(&h) * ggx.g2(wo, &wi) / (4.0 * wo.z * wi.z);
+                sum += brdf * wi.z * 2.0 * PI / (4 * n * n) as f32;
+            }
+        }
+        sum
+    }
+
+    #[test]
+    fn visible_normals() {
+        let wo = Vec3::new(0.6, -0.2, 0.5).unit_vector();
+        for &(roughness, anisotropy) in &[(0.05, 0.0), (0.5, 0.0), (0.5, 0.8), (1.0, 0.3)] {
+            let ggx = TrowbridgeReitz::from_roughness(roughness, anisotropy);
+            let n = 20000;
+            let mut estimate: f32 = 0.0;
+            for _ in 0..n {
+                let h: Vec3 = ggx.sample_visible_normal(&wo);
+                assert!(h.z > 0.0 && (h.length() - 1.0).abs() < 1e-4);
+                let wi: Vec3 = reflect(&wo, &h);
+                if wi.z > 0.0 {
+                    let weight: f32 = ggx.g2(&wo, &wi) / ggx.g1(&wo);
+                    assert!(weight <= 1.0 + 1e-5);
+                    estimate += weight / n as f32;
+                }
+            }
+            // a near mirror keeps all the energy, rough surfaces lose some
+            // to the missing multiple bounces
+            if roughness < 0.1 {
+                assert!(estimate > 0.99);
+            } else {
+                let expected: f32 = albedo(&ggx, &wo);
+                assert!(
+                    (estimate - expected).abs() < 0.01,
+                    "{} != {}",
+                    estimate,
+                    expected
+                );
+            }
+        }
+    }
+}
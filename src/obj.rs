@@ -0,0 +1,516 @@
+use crate::hitable::Hitable;
+use crate::hitable_list::HitableList;
+use crate::material::*;
+use crate::mesh::{vertex_normals, MeshData, MeshError};
+use crate::texture::*;
+use crate::vec3::Vec3;
+
+use image;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+// Indices into the attribute arrays of `ObjData` for one corner of a face
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjVertex {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+// Faces sharing the same group name and material
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    pub polygons: Vec<Vec<ObjVertex>>, // the faces as written, triangulated by `mesh_data`
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjData {
+    pub positions: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub normals: Vec<Vec3>,
+    pub groups: Vec<ObjGroup>,
+    pub mtllibs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub kd: Vec3,
+    pub ks: Vec3,
+    pub ke: Vec3,
+    pub ns: f32,
+    pub ni: f32,
+    pub dissolve: f32,
+    pub illum: u32,
+    pub map_kd: Option<String>,
+}
+
+impl MtlMaterial {
+    pub fn new(name: &str) -> Self {
+        MtlMaterial {
+            name: name.to_string(),
+            kd: Vec3::new(0.8, 0.8, 0.8),
+            ks: Vec3::zeros(),
+            ke: Vec3::zeros(),
+            ns: 0.0,
+            ni: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+// Parser state shared by the OBJ and MTL readers, used to report errors with
+// the position of the offending line
+struct LineParser<'a> {
+    file: &'a str,
+    line: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn error(&self, message: String) -> MeshError {
+        MeshError::Parse(format!("{}:{}: {}", self.file, self.line, message))
+    }
+
+    fn floats(&self, args: &[&str], n: usize) -> Result<Vec<f32>, MeshError> {
+        if args.len() < n {
+            return Err(self.error(format!("expected {} numbers, found {}", n, args.len())));
+        }
+        args[..n]
+            .iter()
+            .map(|s| {
+                s.parse::<f32>()
+                    .map_err(|_| self.error(format!("invalid number `{}`", s)))
+            })
+            .collect()
+    }
+
+    fn vec3(&self, args: &[&str]) -> Result<Vec3, MeshError> {
+        let v = self.floats(args, 3)?;
+        Ok(Vec3::new(v[0], v[1], v[2]))
+    }
+
+    fn float(&self, args: &[&str]) -> Result<f32, MeshError> {
+        Ok(self.floats(args, 1)?[0])
+    }
+
+    // OBJ indices start at 1, and negative ones count back from the last element
+    fn index(&self, s: &str, len: usize) -> Result<usize, MeshError> {
+        let i: i64 = s
+            .parse()
+            .map_err(|_| self.error(format!("invalid index `{}`", s)))?;
+        let idx = if i > 0 { i - 1 } else { len as i64 + i };
+        if i == 0 || idx < 0 || idx >= len as i64 {
+            Err(self.error(format!("index {} out of range", i)))
+        } else {
+            Ok(idx as usize)
+        }
+    }
+}
+
+pub fn parse_obj(source: &str, file: &str) -> Result<ObjData, MeshError> {
+    let mut data = ObjData::default();
+    let mut group_name = String::from("default");
+    let mut material: Option<String> = None;
+    let mut parser = LineParser { file, line: 0 };
+
+    for (i, line) in source.lines().enumerate() {
+        parser.line = i + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+        match keyword {
+            "v" => data.positions.push(parser.vec3(&args)?),
+            "vn" => data.normals.push(parser.vec3(&args)?),
+            "vt" => {
+                let uv = parser.floats(&args, 2)?;
+                data.uvs.push((uv[0], uv[1]));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(parser.error("a face needs at least 3 vertices".to_string()));
+                }
+                let mut polygon = vec![];
+                for arg in &args {
+                    polygon.push(parse_face_vertex(&parser, arg, &data)?);
+                }
+                let group = current_group(&mut data, &group_name, &material);
+                group.polygons.push(polygon);
+            }
+            "g" | "o" => {
+                group_name = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+            }
+            "usemtl" => {
+                if args.is_empty() {
+                    return Err(parser.error("`usemtl` needs a material name".to_string()));
+                }
+                material = Some(args.join(" "));
+            }
+            "mtllib" => data.mtllibs.extend(args.iter().map(|s| s.to_string())),
+            // smoothing groups, lines, points, free-form geometry etc.
+            _ => {}
+        }
+    }
+    data.groups.retain(|group| !group.polygons.is_empty());
+    Ok(data)
+}
+
+fn parse_face_vertex(
+    parser: &LineParser,
+    arg: &str,
+    data: &ObjData,
+) -> Result<ObjVertex, MeshError> {
+    // v, v/vt, v//vn or v/vt/vn
+    let mut parts = arg.split('/');
+    let position = parser.index(parts.next().unwrap_or(""), data.positions.len())?;
+    let uv = match parts.next() {
+        Some(s) if !s.is_empty() => Some(parser.index(s, data.uvs.len())?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(s) if !s.is_empty() => Some(parser.index(s, data.normals.len())?),
+        _ => None,
+    };
+    if parts.next().is_some() {
+        return Err(parser.error(format!("invalid face vertex `{}`", arg)));
+    }
+    Ok(ObjVertex {
+        position,
+        uv,
+        normal,
+    })
+}
+
+fn current_group<'a>(
+    data: &'a mut ObjData,
+    name: &str,
+    material: &Option<String>,
+) -> &'a mut ObjGroup {
+    let found = data
+        .groups
+        .iter()
+        .position(|group| group.name == name && &group.material == material);
+    let idx = match found {
+        Some(idx) => idx,
+        None => {
+            data.groups.push(ObjGroup {
+                name: name.to_string(),
+                material: material.clone(),
+                polygons: vec![],
+            });
+            data.groups.len() - 1
+        }
+    };
+    &mut data.groups[idx]
+}
+
+pub fn parse_mtl(source: &str, file: &str) -> Result<Vec<MtlMaterial>, MeshError> {
+    let mut materials: Vec<MtlMaterial> = vec![];
+    let mut parser = LineParser { file, line: 0 };
+
+    for (i, line) in source.lines().enumerate() {
+        parser.line = i + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(parser.error("`newmtl` needs a material name".to_string()));
+            }
+            materials.push(MtlMaterial::new(&args.join(" ")));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(parser.error(format!("`{}` before any `newmtl`", keyword))),
+        };
+        match keyword {
+            "Kd" => material.kd = parser.vec3(&args)?,
+            "Ks" => material.ks = parser.vec3(&args)?,
+            "Ke" => material.ke = parser.vec3(&args)?,
+            "Ns" => material.ns = parser.float(&args)?,
+            "Ni" => material.ni = parser.float(&args)?,
+            "d" => material.dissolve = parser.float(&args)?,
+            "Tr" => material.dissolve = 1.0 - parser.float(&args)?,
+            "illum" => {
+                material.illum = parser.float(&args)? as u32;
+            }
+            "map_Kd" => match args.last() {
+                // options such as `-s 1 1 1` come before the file name
+                Some(name) => material.map_kd = Some(name.to_string()),
+                None => return Err(parser.error("`map_Kd` needs a file name".to_string())),
+            },
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+// Loads a Wavefront OBJ file along with the MTL libraries it refers to. Each
+// group/material pair becomes one `TriangleMesh`.
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<HitableList, MeshError> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let source = fs::read_to_string(path).map_err(|e| MeshError::Io(path.to_path_buf(), e))?;
+    let data = parse_obj(&source, &path.display().to_string())?;
+
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+    for lib in &data.mtllibs {
+        let mtl_path = dir.join(lib);
+        let source =
+            fs::read_to_string(&mtl_path).map_err(|e| MeshError::Io(mtl_path.clone(), e))?;
+        for material in parse_mtl(&source, &mtl_path.display().to_string())? {
+            materials.insert(material.name.clone(), material);
+        }
+    }
+
+    let mut textures: HashMap<String, Arc<ImageTexture>> = HashMap::new();
+    let mut hitables: Vec<Box<dyn Hitable>> = vec![];
+    for group in &data.groups {
+        let material = match &group.material {
+            Some(name) => Some(
+                materials
+                    .get(name)
+                    .ok_or_else(|| MeshError::MissingMaterial(name.clone()))?,
+            ),
+            None => None,
+        };
+        let texture = match material.and_then(|m| m.map_kd.as_ref()) {
+            Some(name) => Some(load_texture(&dir.join(name), name, &mut textures)?),
+            None => None,
+        };
+        hitables.push(group_to_mesh(&data, group, material, texture));
+    }
+    Ok(HitableList { hitables })
+}
+
+fn load_texture(
+    path: &Path,
+    name: &str,
+    cache: &mut HashMap<String, Arc<ImageTexture>>,
+) -> Result<Arc<ImageTexture>, MeshError> {
+    if let Some(texture) = cache.get(name) {
+        return Ok(texture.clone());
+    }
+    let image_data = image::open(path)
+        .map_err(|e| MeshError::Image(path.to_path_buf(), e))?
+        .to_rgb();
+    let (nx, ny): (u32, u32) = image_data.dimensions();
+    let texture = Arc::new(ImageTexture::new(image_data.into_raw(), nx, ny));
+    cache.insert(name.to_string(), texture.clone());
+    Ok(texture)
+}
+
+impl ObjGroup {
+    // Vertex buffers of the group, with one vertex per distinct
+    // position/uv/normal combination used by its faces, and the polygons
+    // split into fans of triangles. Where only some faces have normals, the
+    // others get smooth normals of their own.
+    pub fn mesh_data(&self, data: &ObjData) -> MeshData {
+        let mut buffers = MeshData::default();
+        let has_uvs = self.polygons.iter().flatten().any(|v| v.uv.is_some());
+        let has_normals = self.polygons.iter().flatten().any(|v| v.normal.is_some());
+        let mut missing_normals: Vec<usize> = vec![];
+        let mut vertex_ids: HashMap<ObjVertex, u32> = HashMap::new();
+        for polygon in &self.polygons {
+            let corners: Vec<u32> = polygon
+                .iter()
+                .map(|vertex| {
+                    *vertex_ids.entry(*vertex).or_insert_with(|| {
+                        buffers.positions.push(data.positions[vertex.position]);
+                        if has_uvs {
+                            buffers
+                                .uvs
+                                .push(vertex.uv.map_or((0.0, 0.0), |i| data.uvs[i]));
+                        }
+                        if has_normals {
+                            if vertex.normal.is_none() {
+                                missing_normals.push(buffers.normals.len());
+                            }
+                            buffers
+                                .normals
+                                .push(vertex.normal.map_or(Vec3::zeros(), |i| data.normals[i]));
+                        }
+                        buffers.positions.len() as u32 - 1
+                    })
+                })
+                .collect();
+            buffers
+                .indices
+                .extend((1..corners.len() - 1).map(|k| [corners[0], corners[k], corners[k + 1]]));
+            buffers.polygons.push(corners);
+        }
+        if !missing_normals.is_empty() {
+            let normals: Vec<Vec3> = vertex_normals(&buffers.positions, &buffers.indices);
+            for i in missing_normals {
+                buffers.normals[i] = normals[i];
+            }
+        }
+        buffers
+    }
+}
+
+fn group_to_mesh(
+    data: &ObjData,
+    group: &ObjGroup,
+    material: Option<&MtlMaterial>,
+    texture: Option<Arc<ImageTexture>>,
+) -> Box<dyn Hitable> {
+    let buffers = group.mesh_data(data);
+    let m = match material {
+        Some(m) => m,
+        None => {
+            return make_mesh(
+                buffers,
+                Lambertian::new(ConstantTexture::new(0.8, 0.8, 0.8)),
+            )
+        }
+    };
+    if max_component(&m.ke) > 0.0 {
+        make_mesh(
+            buffers,
+            DiffuseLight::new(ConstantTexture::new(m.ke.x, m.ke.y, m.ke.z)),
+        )
+    } else if m.dissolve < 1.0 || [4, 6, 7, 9].contains(&m.illum) {
+        // the MTL default of `Ni 1` would make the glass invisible
+        let refract_idx: f32 = if m.ni > 1.0 { m.ni } else { 1.5 };
+        make_mesh(buffers, Dielectric::new(refract_idx))
+    } else if m.illum == 3 || max_component(&m.ks) > max_component(&m.kd) {
+        // sharper highlights (larger `Ns`) mean a less fuzzy reflection
+        let fuzz: f32 = (2.0 / (m.ns.max(0.0) + 2.0)).sqrt();
+        make_mesh(buffers, Metal::new((m.ks.x, m.ks.y, m.ks.z), fuzz))
+    } else if let Some(texture) = texture {
+        make_mesh(buffers, Lambertian::new(texture))
+    } else {
+        make_mesh(
+            buffers,
+            Lambertian::new(ConstantTexture::new(m.kd.x, m.kd.y, m.kd.z)),
+        )
+    }
+}
+
+fn make_mesh<M: Material + 'static>(buffers: MeshData, material: M) -> Box<dyn Hitable> {
+    Box::new(buffers.into_mesh(material))
+}
+
+fn max_component(v: &Vec3) -> f32 {
+    v.x.max(v.y).max(v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+# a unit quad split in two groups
+mtllib quad.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g front
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g back
+f -1 -2 -3
+";
+
+    #[test]
+    fn parse_groups_and_triangulate() {
+        let data = parse_obj(QUAD, "quad.obj").unwrap();
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.mtllibs, vec!["quad.mtl".to_string()]);
+        assert_eq!(data.groups.len(), 2);
+
+        let front = &data.groups[0];
+        assert_eq!(front.name, "front");
+        assert_eq!(front.material, Some("red".to_string()));
+        assert_eq!(front.polygons.len(), 1);
+        assert_eq!(front.polygons[0][3].position, 3);
+        assert_eq!(front.polygons[0][3].uv, Some(3));
+        assert_eq!(front.polygons[0][3].normal, Some(0));
+
+        let back = &data.groups[1];
+        assert_eq!(back.material, Some("red".to_string()));
+        let positions: Vec<usize> = back.polygons[0].iter().map(|v| v.position).collect();
+        assert_eq!(positions, vec![3, 2, 1]);
+        assert_eq!(back.polygons[0][0].uv, None);
+
+        let buffers = front.mesh_data(&data);
+        assert_eq!(buffers.positions.len(), 4);
+        assert_eq!(buffers.uvs.len(), 4);
+        assert_eq!(buffers.indices, vec![[0, 1, 2], [0, 2, 3]]);
+
+        // the back has no normals of its own, but shares a group with a
+        // face that has
+        let source = QUAD.replace("g back\n", "");
+        let data = parse_obj(&source, "quad.obj").unwrap();
+        let buffers = data.groups[0].mesh_data(&data);
+        assert_eq!(buffers.normals.len(), buffers.positions.len());
+        for (i, n) in buffers.normals.iter().enumerate() {
+            let expected: f32 = if i < 4 { 1.0 } else { -1.0 };
+            assert_eq!(*n, Vec3::new(0.0, 0.0, expected));
+        }
+    }
+
+    #[test]
+    fn parse_materials() {
+        let source = "
+newmtl red
+Kd 0.8 0.1 0.1
+Ns 250
+newmtl glass
+Ni 1.45
+d 0.2
+newmtl lamp
+Ke 4 4 4
+map_Kd -s 1 1 1 lamp.png
+";
+        let materials = parse_mtl(source, "quad.mtl").unwrap();
+        assert_eq!(materials.len(), 3);
+        assert_eq!(materials[0].kd, Vec3::new(0.8, 0.1, 0.1));
+        assert_eq!(materials[0].ns, 250.0);
+        assert_eq!(materials[1].ni, 1.45);
+        assert_eq!(materials[1].dissolve, 0.2);
+        assert_eq!(materials[2].ke, Vec3::new(4.0, 4.0, 4.0));
+        assert_eq!(materials[2].map_kd, Some("lamp.png".to_string()));
+    }
+
+    #[test]
+    fn malformed_files() {
+        let err = parse_obj("v 0 0 0\nv 1 0\n", "bad.obj").unwrap_err();
+        assert_eq!(err.to_string(), "bad.obj:2: expected 3 numbers, found 2");
+        let err = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", "bad.obj").unwrap_err();
+        assert_eq!(err.to_string(), "bad.obj:4: index 4 out of range");
+        let err = parse_obj("v 0 0 0\nf 1 1\n", "bad.obj").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "bad.obj:2: a face needs at least 3 vertices"
+        );
+        let err = parse_mtl("Kd 1 1 1\n", "bad.mtl").unwrap_err();
+        assert_eq!(err.to_string(), "bad.mtl:1: `Kd` before any `newmtl`");
+        assert!(load_obj("no/such/file.obj").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::material::Material;
+use crate::mesh::{MeshData, MeshError, TriangleMesh};
+use crate::vec3::Vec3;
+
+use std::fs;
+use std::path::Path;
+
+// Bicubic Bézier patch. Control points are stored row by row, `u` running
+// along a row and `v` across the rows, and the surface faces towards
+// dP/du x dP/dv, which is outwards for the Utah teapot data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BezierPatch {
+    pub points: [Vec3; 16],
+}
+
+// Cubic Bernstein basis and its derivative
+fn bernstein(t: f32) -> ([f32; 4], [f32; 4]) {
+    let s: f32 = 1.0 - t;
+    (
+        [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t],
+        [
+            -3.0 * s * s,
+            3.0 * s * s - 6.0 * s * t,
+            6.0 * s * t - 3.0 * t * t,
+            3.0 * t * t,
+        ],
+    )
+}
+
+impl BezierPatch {
+    pub fn new(points: [Vec3; 16]) -> Self {
+        BezierPatch { points }
+    }
+
+    // Position and both partial derivatives
+    fn evaluate(&self, u: f32, v: f32) -> (Vec3, Vec3, Vec3) {
+        let (bu, du) = bernstein(u);
+        let (bv, dv) = bernstein(v);
+        let mut p = Vec3::zeros();
+        let mut dp_du = Vec3::zeros();
+        let mut dp_dv = Vec3::zeros();
+        for i in 0..4 {
+            for j in 0..4 {
+                let c: Vec3 = self.points[4 * i + j];
+                p += bv[i] * bu[j] * c;
+                dp_du += bv[i] * du[j] * c;
+                dp_dv += dv[i] * bu[j] * c;
+            }
+        }
+        (p, dp_du, dp_dv)
+    }
+
+    pub fn point_at(&self, u: f32, v: f32) -> Vec3 {
+        self.evaluate(u, v).0
+    }
+
+    pub fn normal_at(&self, u: f32, v: f32) -> Vec3 {
+        let (_, dp_du, dp_dv) = self.evaluate(u, v);
+        let n: Vec3 = dp_du.cross(&dp_dv);
+        if n.squared_length() > 1e-12 {
+            return n.unit_vector();
+        }
+        // collapsed edge, like the top of the teapot lid: look a little
+        // further into the patch
+        let (_, dp_du, dp_dv) =
+            self.evaluate(u + 1e-3 * (0.5 - u).signum(), v + 1e-3 * (0.5 - v).signum());
+        dp_du.cross(&dp_dv).unit_vector()
+    }
+}
+
+// Triangles for a grid of `divisions` by `divisions` quads per patch, with
+// exact normals and each patch's (u, v) as texture coordinates
+pub fn tessellate(patches: &[BezierPatch], divisions: usize) -> MeshData {
+    let divisions: usize = divisions.max(1);
+    let n: usize = divisions + 1;
+    let mut data = MeshData::default();
+    for patch in patches {
+        let base = data.positions.len() as u32;
+        for i in 0..n {
+            for j in 0..n {
+                let u: f32 = j as f32 / divisions as f32;
+                let v: f32 = i as f32 / divisions as f32;
+                data.positions.push(patch.point_at(u, v));
+                data.normals.push(patch.normal_at(u, v));
+                data.uvs.push((u, v));
+            }
+        }
+        for i in 0..divisions {
+            for j in 0..divisions {
+                let at = |i: usize, j: usize| base + (i * n + j) as u32;
+                data.indices
+                    .push([at(i, j), at(i, j + 1), at(i + 1, j + 1)]);
+                data.indices
+                    .push([at(i, j), at(i + 1, j + 1), at(i + 1, j)]);
+            }
+        }
+    }
+    data
+}
+
+fn parse_error(message: &str) -> MeshError {
+    MeshError::Parse(format!("patches: {}", message))
+}
+
+// The format of the Utah teapot data: the number of patches, then 16
+// one-based vertex indices per patch, then the number of vertices and their
+// coordinates, all separated by commas or whitespace
+pub fn parse_patches(source: &str) -> Result<Vec<BezierPatch>, MeshError> {
+    let mut tokens = source
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty());
+    let mut count = |what: &str| -> Result<usize, MeshError> {
+        tokens
+            .next()
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or_else(|| parse_error(&format!("expected {}", what)))
+    };
+
+    let patch_count: usize = count("the number of patches")?;
+    let mut patch_indices: Vec<usize> = Vec::with_capacity(16 * patch_count);
+    for _ in 0..16 * patch_count {
+        patch_indices.push(count("a vertex index")?);
+    }
+    let vertex_count: usize = count("the number of vertices")?;
+    let mut vertices: Vec<Vec3> = Vec::with_capacity(vertex_count);
+    for _ in 0..vertex_count {
+        let mut coords = [0.0; 3];
+        for c in &mut coords {
+            *c = tokens
+                .next()
+                .and_then(|s| s.parse::<f32>().ok())
+                .ok_or_else(|| parse_error("expected a vertex coordinate"))?;
+        }
+        vertices.push(Vec3::new(coords[0], coords[1], coords[2]));
+    }
+
+    patch_indices
+        .chunks(16)
+        .map(|indices| {
+            let mut points = [Vec3::zeros(); 16];
+            for (point, &i) in points.iter_mut().zip(indices) {
+                if i == 0 || i > vertices.len() {
+                    return Err(parse_error("patch refers to a missing vertex"));
+                }
+                *point = vertices[i - 1];
+            }
+            Ok(BezierPatch::new(points))
+        })
+        .collect()
+}
+
+// Loads a patch file like the Utah teapot, tessellated into a mesh
+pub fn load_patches<P: AsRef<Path>, M: Material>(
+    path: P,
+    divisions: usize,
+    material: M,
+) -> Result<TriangleMesh<M>, MeshError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|e| MeshError::Io(path.to_path_buf(), e))?;
+    Ok(tessellate(&parse_patches(&source)?, divisions).into_mesh(material))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A dome over the unit square, the middle four points raised
+    const DOME: &str = "1
+1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16
+16
+0.0, 0.0, 0.0
+1.0, 0.0, 0.0
+2.0, 0.0, 0.0
+3.0, 0.0, 0.0
+0.0, 1.0, 0.0
+1.0, 1.0, 1.5
+2.0, 1.0, 1.5
+3.0, 1.0, 0.0
+0.0, 2.0, 0.0
+1.0, 2.0, 1.5
+2.0, 2.0, 1.5
+3.0, 2.0, 0.0
+0.0, 3.0, 0.0
+1.0, 3.0, 0.0
+2.0, 3.0, 0.0
+3.0, 3.0, 0.0
+";
+
+    #[test]
+    fn dome() {
+        let patches = parse_patches(DOME).unwrap();
+        assert_eq!(patches.len(), 1);
+        let patch = &patches[0];
+        assert_eq!(patch.point_at(1.0, 0.0), Vec3::new(3.0, 0.0, 0.0));
+        // the middle is 9/16 of the way up towards the raised points
+        let top: Vec3 = patch.point_at(0.5, 0.5);
+        assert!((top - Vec3::new(1.5, 1.5, 1.5 * 0.5625)).length() < 1e-5);
+        assert!((patch.normal_at(0.5, 0.5) - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-5);
+
+        let data = tessellate(&patches, 4);
+        assert_eq!(data.positions.len(), 25);
+        assert_eq!(data.indices.len(), 32);
+        assert_eq!(data.uvs[24], (1.0, 1.0));
+        // triangles wound the same way as the normals
+        for face in &data.indices {
+            let [a, b, c] = face.map(|i| data.positions[i as usize]);
+            assert!((b - a).cross(&(c - a)).z > 0.0);
+        }
+
+        assert!(parse_patches("1\n1, 2, 3\n").is_err());
+        assert!(parse_patches(&DOME.replace("16\n0.0", "15\n0.0")).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// Infinite plane through `point`, e.g. a ground that never ends. Textures
+// repeat every `tile_size` along both directions of the plane.
+pub struct Plane<M: Material> {
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub tile_size: f32,
+    tangent: Vec3,   // direction of `u`
+    bitangent: Vec3, // direction of `v`
+    pub material: M,
+}
+
+impl<M: Material> Plane<M> {
+    pub fn new(point: Vec3, normal: Vec3, material: M) -> Self {
+        let normal: Vec3 = normal.unit_vector();
+        // any direction in the plane will do, x for a horizontal one
+        let helper = if normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent: Vec3 = (helper - helper.dot(&normal) * normal).unit_vector();
+        Plane {
+            point,
+            normal,
+            tile_size: 1.0,
+            tangent,
+            bitangent: tangent.cross(&normal),
+            material,
+        }
+    }
+
+    pub fn with_tile_size(mut self, tile_size: f32) -> Self {
+        self.tile_size = tile_size;
+        self
+    }
+}
+
+impl<M: Material> Hitable for Plane<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom: f32 = self.normal.dot(&r.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t: f32 = self.normal.dot(&(self.point - r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let p: Vec3 = r.point_at_parameter(t);
+        let a: f32 = (p - self.point).dot(&self.tangent) / self.tile_size;
+        let b: f32 = (p - self.point).dot(&self.bitangent) / self.tile_size;
+        Some(HitRecord {
+            t,
+            u: a - a.floor(),
+            v: b - b.floor(),
+            p,
+            normal: self.normal,
+            color: None,
+            material: &self.material,
+        })
+    }
+
+    // unbounded, BVHs keep it aside and test it against every ray
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::{BVHNode, FlatBVH};
+    use crate::hitable_list::HitableList;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::texture::ConstantTexture;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn scene() -> HitableList {
+        let hitables: Vec<Box<dyn Hitable>> = vec![
+            Box::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0, white())),
+            Box::new(
+                Plane::new(Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0), white()).with_tile_size(4.0),
+            ),
+            Box::new(Sphere::new(Vec3::new(5.0, 1.0, 0.0), 1.0, white())),
+        ];
+        HitableList { hitables }
+    }
+
+    #[test]
+    fn ground_in_bvh() {
+        let list = scene();
+        let flat = FlatBVH::new(scene(), 0.0, 1.0);
+        let node = BVHNode::new(scene(), 0.0, 1.0);
+        assert!(flat.bounding_box(0.0, 1.0).is_none());
+        assert!(node.bounding_box(0.0, 1.0).is_none());
+
+        let rays = [
+            // onto the ground far away from everything else
+            Ray::new(Vec3::new(100.0, 3.0, -50.0), Vec3::new(1.0, -1.0, 0.3), 0.0),
+            // onto a sphere standing on the ground
+            Ray::new(Vec3::new(0.0, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0),
+            // up into the sky
+            Ray::new(Vec3::new(0.0, 1.0, 5.0), Vec3::new(0.0, 1.0, 0.0), 0.0),
+        ];
+        for r in rays.iter() {
+            let expected = list.hit(r, 0.001, f32::MAX).map(|rec| rec.t);
+            assert_eq!(flat.hit(r, 0.001, f32::MAX).map(|rec| rec.t), expected);
+            assert_eq!(node.hit(r, 0.001, f32::MAX).map(|rec| rec.t), expected);
+        }
+        let rec = flat.hit(&rays[0], 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-4);
+        assert!((rec.u - 0.75).abs() < 1e-4);
+
+        let only_ground = FlatBVH::new(
+            HitableList {
+                hitables: vec![Box::new(Plane::new(
+                    Vec3::zeros(),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    white(),
+                ))],
+            },
+            0.0,
+            1.0,
+        );
+        assert!(only_ground.hit(&rays[0], 0.001, f32::MAX).is_some());
+    }
+}
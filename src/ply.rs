@@ -0,0 +1,451 @@
+use crate::material::Material;
+use crate::mesh::{MeshData, MeshError, TriangleMesh};
+use crate::vec3::Vec3;
+
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::I8),
+            "uchar" | "uint8" => Some(ScalarType::U8),
+            "short" | "int16" => Some(ScalarType::I16),
+            "ushort" | "uint16" => Some(ScalarType::U16),
+            "int" | "int32" => Some(ScalarType::I32),
+            "uint" | "uint32" => Some(ScalarType::U32),
+            "float" | "float32" => Some(ScalarType::F32),
+            "double" | "float64" => Some(ScalarType::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    // Scale that maps integer color channels to 0..1
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::U8 | ScalarType::I8 => 1.0 / 255.0,
+            ScalarType::U16 | ScalarType::I16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar(String, ScalarType),
+    List(String, ScalarType, ScalarType), // name, type of the count, type of the items
+}
+
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+fn parse_error(message: String) -> MeshError {
+    MeshError::Parse(format!("PLY: {}", message))
+}
+
+// Parses the header and returns it with the offset of the body
+fn parse_header(bytes: &[u8]) -> Result<(Header, usize), MeshError> {
+    let mut format: Option<Format> = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut pos: usize = 0;
+    let mut first = true;
+
+    loop {
+        let end = match bytes[pos..].iter().position(|&b| b == b'\n') {
+            Some(end) => pos + end,
+            None => return Err(parse_error("missing `end_header`".to_string())),
+        };
+        let line = std::str::from_utf8(&bytes[pos..end])
+            .map_err(|_| parse_error("header is not valid text".to_string()))?
+            .trim();
+        pos = end + 1;
+        if first {
+            if line != "ply" {
+                return Err(parse_error("missing `ply` magic number".to_string()));
+            }
+            first = false;
+            continue;
+        }
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"format") => {
+                format = match tokens.get(1) {
+                    Some(&"ascii") => Some(Format::Ascii),
+                    Some(&"binary_little_endian") => Some(Format::BinaryLittleEndian),
+                    Some(&"binary_big_endian") => Some(Format::BinaryBigEndian),
+                    _ => return Err(parse_error(format!("unknown format `{}`", line))),
+                }
+            }
+            Some(&"element") => {
+                if tokens.len() != 3 {
+                    return Err(parse_error(format!("invalid element `{}`", line)));
+                }
+                let count = tokens[2]
+                    .parse()
+                    .map_err(|_| parse_error(format!("invalid element count `{}`", tokens[2])))?;
+                elements.push(Element {
+                    name: tokens[1].to_string(),
+                    count,
+                    properties: vec![],
+                });
+            }
+            Some(&"property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| parse_error("property before any element".to_string()))?;
+                let scalar = |name: &str| {
+                    ScalarType::parse(name)
+                        .ok_or_else(|| parse_error(format!("unknown property type `{}`", name)))
+                };
+                let property = match tokens.as_slice() {
+                    ["property", "list", count, item, name] => {
+                        Property::List(name.to_string(), scalar(count)?, scalar(item)?)
+                    }
+                    ["property", ty, name] => Property::Scalar(name.to_string(), scalar(ty)?),
+                    _ => return Err(parse_error(format!("invalid property `{}`", line))),
+                };
+                element.properties.push(property);
+            }
+            Some(&"end_header") => break,
+            // comments, obj_info and blank lines
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| parse_error("missing `format`".to_string()))?;
+    Ok((Header { format, elements }, pos))
+}
+
+// Reads the values of the body one at a time, whatever the format
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary {
+        bytes: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, MeshError> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| parse_error("unexpected end of file".to_string()))?;
+                token
+                    .parse::<f64>()
+                    .map_err(|_| parse_error(format!("invalid number `{}`", token)))
+            }
+            Body::Binary {
+                bytes,
+                pos,
+                big_endian,
+            } => {
+                let size = ty.size();
+                if *pos + size > bytes.len() {
+                    return Err(parse_error("unexpected end of file".to_string()));
+                }
+                let mut buf = [0u8; 8];
+                buf[..size].copy_from_slice(&bytes[*pos..*pos + size]);
+                *pos += size;
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+                let value = match ty {
+                    ScalarType::I8 => f64::from(buf[0] as i8),
+                    ScalarType::U8 => f64::from(buf[0]),
+                    ScalarType::I16 => f64::from(i16::from_le_bytes([buf[0], buf[1]])),
+                    ScalarType::U16 => f64::from(u16::from_le_bytes([buf[0], buf[1]])),
+                    ScalarType::I32 => {
+                        f64::from(i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+                    }
+                    ScalarType::U32 => {
+                        f64::from(u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+                    }
+                    ScalarType::F32 => {
+                        f64::from(f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]))
+                    }
+                    ScalarType::F64 => f64::from_le_bytes(buf),
+                };
+                Ok(value)
+            }
+        }
+    }
+}
+
+// Positions of the vertex properties we know about
+#[derive(Default)]
+struct VertexLayout {
+    position: Option<[usize; 3]>,
+    normal: Option<[usize; 3]>,
+    uv: Option<[usize; 2]>,
+    color: Option<([usize; 3], f64)>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Self {
+        let find = |names: &[&str]| {
+            element.properties.iter().position(|p| match p {
+                Property::Scalar(name, _) => names.contains(&name.as_str()),
+                Property::List(..) => false,
+            })
+        };
+        let find3 = |a: &[&str], b: &[&str], c: &[&str]| match (find(a), find(b), find(c)) {
+            (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+            _ => None,
+        };
+        let uv = match (
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ) {
+            (Some(u), Some(v)) => Some([u, v]),
+            _ => None,
+        };
+        let color = find3(&["red", "r"], &["green", "g"], &["blue", "b"]).map(|idx| {
+            let scale = match &element.properties[idx[0]] {
+                Property::Scalar(_, ty) => ty.color_scale(),
+                Property::List(..) => 1.0,
+            };
+            (idx, scale)
+        });
+        VertexLayout {
+            position: find3(&["x"], &["y"], &["z"]),
+            normal: find3(&["nx"], &["ny"], &["nz"]),
+            uv,
+            color,
+        }
+    }
+}
+
+pub fn parse_ply(bytes: &[u8]) -> Result<MeshData, MeshError> {
+    let (header, offset) = parse_header(bytes)?;
+    let mut body = match header.format {
+        Format::Ascii => Body::Ascii(
+            std::str::from_utf8(&bytes[offset..])
+                .map_err(|_| parse_error("body is not valid text".to_string()))?
+                .split_ascii_whitespace(),
+        ),
+        format => Body::Binary {
+            bytes: &bytes[offset..],
+            pos: 0,
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut data = MeshData::default();
+    let mut values: Vec<f64> = vec![];
+    let mut polygon: Vec<u32> = vec![];
+    for element in &header.elements {
+        let layout = VertexLayout::new(element);
+        if element.name == "vertex" && layout.position.is_none() {
+            return Err(parse_error(
+                "vertices have no x, y, z properties".to_string(),
+            ));
+        }
+        for _ in 0..element.count {
+            values.clear();
+            polygon.clear();
+            for property in &element.properties {
+                match property {
+                    Property::Scalar(_, ty) => values.push(body.read(*ty)?),
+                    Property::List(name, count_ty, item_ty) => {
+                        let count = body.read(*count_ty)? as usize;
+                        let is_face = name == "vertex_indices" || name == "vertex_index";
+                        for _ in 0..count {
+                            let item = body.read(*item_ty)?;
+                            if is_face {
+                                polygon.push(item as u32);
+                            }
+                        }
+                        values.push(0.0);
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let vec3 = |idx: [usize; 3], scale: f64| {
+                        Vec3::new(
+                            (values[idx[0]] * scale) as f32,
+                            (values[idx[1]] * scale) as f32,
+                            (values[idx[2]] * scale) as f32,
+                        )
+                    };
+                    if let Some(idx) = layout.position {
+                        data.positions.push(vec3(idx, 1.0));
+                    }
+                    if let Some(idx) = layout.normal {
+                        data.normals.push(vec3(idx, 1.0));
+                    }
+                    if let Some(idx) = layout.uv {
+                        data.uvs
+                            .push((values[idx[0]] as f32, values[idx[1]] as f32));
+                    }
+                    if let Some((idx, scale)) = layout.color {
+                        data.colors.push(vec3(idx, scale));
+                    }
+                }
+                "face" => {
+                    if polygon.len() < 3 {
+                        return Err(parse_error("a face needs at least 3 vertices".to_string()));
+                    }
+                    // fan triangulation
+                    for i in 1..polygon.len() - 1 {
+                        data.indices.push([polygon[0], polygon[i], polygon[i + 1]]);
+                    }
+                    data.polygons.push(polygon.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let n = data.positions.len() as u32;
+    if data.indices.iter().flatten().any(|&i| i >= n) {
+        return Err(parse_error("face refers to a missing vertex".to_string()));
+    }
+    Ok(data)
+}
+
+// Loads an ASCII or binary PLY file. Vertex colors, if any, are available to
+// the material through `VertexColorTexture`.
+pub fn load_ply<P: AsRef<Path>, M: Material>(
+    path: P,
+    material: M,
+) -> Result<TriangleMesh<M>, MeshError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| MeshError::Io(path.to_path_buf(), e))?;
+    Ok(parse_ply(&bytes)?.into_mesh(material))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply
+format {} 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    fn header(format: &str) -> Vec<u8> {
+        HEADER.replace("{}", format).into_bytes()
+    }
+
+    fn check_quad(data: &MeshData) {
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.positions[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(data.colors[1], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert!(data.normals.is_empty());
+    }
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    #[test]
+    fn ascii() {
+        let mut bytes = header("ascii");
+        bytes.extend_from_slice(
+            b"0 0 0 255 255 255\n1 0 0 255 0 0\n1 1 0 0 255 0\n0 1 0 0 0 255\n4 0 1 2 3\n",
+        );
+        check_quad(&parse_ply(&bytes).unwrap());
+    }
+
+    #[test]
+    fn binary() {
+        for &(format, big_endian) in &[("binary_little_endian", false), ("binary_big_endian", true)]
+        {
+            let mut bytes = header(format);
+            let colors = [[255u8, 255, 255], [255, 0, 0], [0, 255, 0], [0, 0, 255]];
+            for (p, c) in POSITIONS.iter().zip(colors.iter()) {
+                for x in p {
+                    if big_endian {
+                        bytes.extend_from_slice(&x.to_be_bytes());
+                    } else {
+                        bytes.extend_from_slice(&x.to_le_bytes());
+                    }
+                }
+                bytes.extend_from_slice(c);
+            }
+            bytes.push(4);
+            for i in 0..4i32 {
+                if big_endian {
+                    bytes.extend_from_slice(&i.to_be_bytes());
+                } else {
+                    bytes.extend_from_slice(&i.to_le_bytes());
+                }
+            }
+            check_quad(&parse_ply(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(parse_ply(b"plx\n").is_err());
+        let mut bytes = header("ascii");
+        bytes.extend_from_slice(b"0 0 0 255 255 255\n");
+        assert_eq!(
+            parse_ply(&bytes).unwrap_err().to_string(),
+            "PLY: unexpected end of file"
+        );
+        let mut bytes = header("ascii");
+        bytes.extend_from_slice(b"0 0 0 1 1 1\n1 0 0 1 1 1\n1 1 0 1 1 1\n0 1 0 1 1 1\n3 0 1 7\n");
+        assert_eq!(
+            parse_ply(&bytes).unwrap_err().to_string(),
+            "PLY: face refers to a missing vertex"
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use crate::hitable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{self, Frame, TrowbridgeReitz};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// A scalar input of `Principled`, either fixed or read from a texture (the
+// mean of its channels)
+#[derive(Clone)]
+pub enum Parameter {
+    Constant(f32),
+    Texture(Arc<dyn Texture>),
+}
+
+impl Parameter {
+    pub fn value_at(&self, rec: &HitRecord) -> f32 {
+        match self {
+            Parameter::Constant(value) => *value,
+            Parameter::Texture(texture) => {
+                let c: Vec3 = texture.value_at(rec);
+                (c.x + c.y + c.z) / 3.0
+            }
+        }
+    }
+}
+
+impl From<f32> for Parameter {
+    fn from(value: f32) -> Self {
+        Parameter::Constant(value)
+    }
+}
+
+impl<T: Texture + 'static> From<T> for Parameter {
+    fn from(texture: T) -> Self {
+        Parameter::Texture(Arc::new(texture))
+    }
+}
+
+// Disney's principled BSDF (Burley, "Physically Based Shading at Disney",
+// 2012, and the 2015 extension to transmission). Every parameter is in
+// [0, 1] except `refract_idx`. Each scattering picks one lobe at random and
+// weights it by how likely that lobe was to be picked.
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Parameter,
+    pub roughness: Parameter,
+    pub specular: Parameter, // 0.5 is 4% reflectance, like glass or plastic
+    pub specular_tint: Parameter,
+    pub sheen: Parameter, // extra grazing reflection for cloth
+    pub sheen_tint: Parameter,
+    pub clearcoat: Parameter, // a second, colorless specular layer
+    pub clearcoat_gloss: Parameter,
+    pub transmission: Parameter, // how much of the non-metal part is glass
+    pub subsurface: Parameter,   // flattens the diffuse part like skin or wax
+    pub refract_idx: f32,
+}
+
+impl Principled {
+    pub fn new<T: Texture + 'static>(base_color: T) -> Self {
+        Principled {
+            base_color: Arc::new(base_color),
+            metallic: 0.0.into(),
+            roughness: 0.5.into(),
+            specular: 0.5.into(),
+            specular_tint: 0.0.into(),
+            sheen: 0.0.into(),
+            sheen_tint: 0.5.into(),
+            clearcoat: 0.0.into(),
+            clearcoat_gloss: 1.0.into(),
+            transmission: 0.0.into(),
+            subsurface: 0.0.into(),
+            refract_idx: 1.5,
+        }
+    }
+
+    pub fn with_metallic<P: Into<Parameter>>(mut self, metallic: P) -> Self {
+        self.metallic = metallic.into();
+        self
+    }
+
+    pub fn with_roughness<P: Into<Parameter>>(mut self, roughness: P) -> Self {
+        self.roughness = roughness.into();
+        self
+    }
+
+    pub fn with_specular<P: Into<Parameter>>(mut self, specular: P) -> Self {
+        self.specular = specular.into();
+        self
+    }
+
+    pub fn with_specular_tint<P: Into<Parameter>>(mut self, specular_tint: P) -> Self {
+        self.specular_tint = specular_tint.into();
+        self
+    }
+
+    pub fn with_sheen<P: Into<Parameter>>(mut self, sheen: P) -> Self {
+        self.sheen = sheen.into();
+        self
+    }
+
+    pub fn with_sheen_tint<P: Into<Parameter>>(mut self, sheen_tint: P) -> Self {
+        self.sheen_tint = sheen_tint.into();
+        self
+    }
+
+    pub fn with_clearcoat<P: Into<Parameter>>(mut self, clearcoat: P) -> Self {
+        self.clearcoat = clearcoat.into();
+        self
+    }
+
+    pub fn with_clearcoat_gloss<P: Into<Parameter>>(mut self, clearcoat_gloss: P) -> Self {
+        self.clearcoat_gloss = clearcoat_gloss.into();
+        self
+    }
+
+    pub fn with_transmission<P: Into<Parameter>>(mut self, transmission: P) -> Self {
+        self.transmission = transmission.into();
+        self
+    }
+
+    pub fn with_subsurface<P: Into<Parameter>>(mut self, subsurface: P) -> Self {
+        self.subsurface = subsurface.into();
+        self
+    }
+
+    pub fn with_refract_idx(mut self, refract_idx: f32) -> Self {
+        self.refract_idx = refract_idx;
+        self
+    }
+}
+
+// Everything looked up at one hit point
+struct Inputs {
+    base_color: Vec3,
+    tint: Vec3, // base color with its luminance taken out
+    metallic: f32,
+    roughness: f32,
+    specular: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_gloss: f32,
+    transmission: f32,
+    subsurface: f32,
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn lerp_color(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    a + t * (b - a)
+}
+
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+// Index of the lobe `pick` falls in, stacking the weights end to end. Rounding
+// can leave some of `pick` past the last lobe, which then goes to the last
+// one that can be picked at all.
+fn pick_lobe(lobes: &[f32; 4], mut pick: f32) -> usize {
+    let last: usize = (0..4).rev().find(|&i| lobes[i] > 0.0).unwrap_or(0);
+    (0..last)
+        .find(|&i| {
+            pick -= lobes[i];
+            lobes[i] > 0.0 && pick < 0.0
+        })
+        .unwrap_or(last)
+}
+
+fn random_cosine_direction() -> Vec3 {
+    let r: f32 = rand::random::<f32>().sqrt();
+    let phi: f32 = 2.0 * PI * rand::random::<f32>();
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1.0 - r * r).max(0.0).sqrt())
+}
+
+impl Principled {
+    fn inputs(&self, rec: &HitRecord) -> Inputs {
+        let base_color: Vec3 = self.base_color.value_at(rec);
+        let luminance: f32 = 0.3 * base_color.x + 0.6 * base_color.y + 0.1 * base_color.z;
+        let clamp = |p: &Parameter| p.value_at(rec).clamp(0.0, 1.0);
+        Inputs {
+            base_color,
+            tint: if luminance > 0.0 {
+                base_color / luminance
+            } else {
+                Vec3::new(1.0, 1.0, 1.0)
+            },
+            metallic: clamp(&self.metallic),
+            roughness: clamp(&self.roughness),
+            specular: clamp(&self.specular),
+            specular_tint: clamp(&self.specular_tint),
+            sheen: clamp(&self.sheen),
+            sheen_tint: clamp(&self.sheen_tint),
+            clearcoat: clamp(&self.clearcoat),
+            clearcoat_gloss: clamp(&self.clearcoat_gloss),
+            transmission: clamp(&self.transmission),
+            subsurface: clamp(&self.subsurface),
+        }
+    }
+
+    // Burley's retro-reflective diffuse, blended towards the subsurface
+    // approximation, plus sheen. Cosine sampled, so the weight is f * pi.
+    fn diffuse(&self, inputs: &Inputs, wo: &Vec3) -> Option<(Vec3, Vec3)> {
+        let wi: Vec3 = random_cosine_direction();
+        let h: Vec3 = (wi + *wo).unit_vector();
+        let cos_d: f32 = wi.dot(&h);
+        let (fl, fv) = (schlick_weight(wi.z), schlick_weight(wo.z));
+
+        let fd90: f32 = 0.5 + 2.0 * cos_d * cos_d * inputs.roughness;
+        let fd: f32 = lerp(1.0, fd90, fl) * lerp(1.0, fd90, fv);
+        let fss90: f32 = cos_d * cos_d * inputs.roughness;
+        let fss: f32 = lerp(1.0, fss90, fl) * lerp(1.0, fss90, fv);
+        let ss: f32 = 1.25 * (fss * (1.0 / (wi.z + wo.z).max(1e-4) - 0.5) + 0.5);
+
+        let sheen_color: Vec3 =
+            lerp_color(Vec3::new(1.0, 1.0, 1.0), inputs.tint, inputs.sheen_tint);
+        let sheen: Vec3 = inputs.sheen * schlick_weight(cos_d) * sheen_color;
+        let weight: Vec3 = inputs.base_color * lerp(fd, ss, inputs.subsurface) + PI * sheen;
+        Some((weight, wi))
+    }
+
+    // GGX reflection with a Schlick Fresnel going from the dielectric
+    // specular color to the base color as the surface gets metallic
+    fn specular(&self, inputs: &Inputs, wo: &Vec3) -> Option<(Vec3, Vec3)> {
+        let ggx = TrowbridgeReitz::from_roughness(inputs.roughness, 0.0);
+        let h: Vec3 = ggx.sample_visible_normal(wo);
+        let wi: Vec3 = microfacet::reflect(wo, &h);
+        if wi.z <= 0.0 {
+            return None;
+        }
+        let dielectric: Vec3 = 0.08
+            * inputs.specular
+            * lerp_color(Vec3::new(1.0, 1.0, 1.0), inputs.tint, inputs.specular_tint);
+        let f0: Vec3 = lerp_color(dielectric, inputs.base_color, inputs.metallic);
+        let fresnel: Vec3 = lerp_color(f0, Vec3::new(1.0, 1.0, 1.0), schlick_weight(wo.dot(&h)));
+        Some((fresnel * (ggx.g2(wo, &wi) / ggx.g1(wo)), wi))
+    }
+
+    // Fixed 4% reflectance layer with the long-tailed GTR1 distribution
+    fn clearcoat(&self, inputs: &Inputs, wo: &Vec3) -> Option<(Vec3, Vec3)> {
+        let alpha: f32 = lerp(0.1, 0.001, inputs.clearcoat_gloss);
+        let a2: f32 = alpha * alpha;
+        let cos_h: f32 = ((1.0 - a2.powf(1.0 - rand::random::<f32>())) / (1.0 - a2)).sqrt();
+        let sin_h: f32 = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+        let phi: f32 = 2.0 * PI * rand::random::<f32>();
+        let h = Vec3::new(sin_h * phi.cos(), sin_h * phi.sin(), cos_h);
+        let wi: Vec3 = microfacet::reflect(wo, &h);
+        if wi.z <= 0.0 || wo.dot(&h) <= 0.0 {
+            return None;
+        }
+        // D cancels out against the pdf of the sampled normal
+        let coat = TrowbridgeReitz::new(0.25, 0.25);
+        let fresnel: f32 = lerp(0.04, 1.0, schlick_weight(wo.dot(&h)));
+        let weight: f32 =
+            0.25 * inputs.clearcoat * fresnel * coat.g1(wo) * coat.g1(&wi) * wo.dot(&h)
+                / (wo.z * cos_h);
+        Some((Vec3::new(weight, weight, weight), wi))
+    }
+
+    // Rough glass tinted by the base color on the way through
+    fn transmission(&self, inputs: &Inputs, wo: &Vec3, eta: f32) -> Option<(Vec3, Vec3)> {
+        let ggx = TrowbridgeReitz::from_roughness(inputs.roughness, 0.0);
+        let h: Vec3 = ggx.sample_visible_normal(wo);
+        let fresnel: f32 = microfacet::fresnel_dielectric(wo.dot(&h), eta);
+        if rand::random::<f32>() < fresnel {
+            let wi: Vec3 = microfacet::reflect(wo, &h);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            let weight: f32 = ggx.g2(wo, &wi) / ggx.g1(wo);
+            Some((Vec3::new(weight, weight, weight), wi))
+        } else {
+            let wi: Vec3 = microfacet::refract(wo, &h, eta)?;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            Some((inputs.base_color * (ggx.g2(wo, &wi) / ggx.g1(wo)), wi))
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let inputs: Inputs = self.inputs(rec);
+        let glass: f32 = (1.0 - inputs.metallic) * inputs.transmission;
+        let inside: bool = r_in.direction.dot(&rec.normal) > 0.0;
+        let normal: Vec3 = if inside { -rec.normal } else { rec.normal };
+        let frame = Frame::from_normal(normal);
+        let wo: Vec3 = frame.to_local(&-r_in.direction.unit_vector());
+
+        let (weight, wi) = if inside && glass > 0.0 {
+            // only the glass part lets rays in
+            self.transmission(&inputs, &wo, 1.0 / self.refract_idx)?
+        } else {
+            let lobes: [f32; 4] = [
+                (1.0 - inputs.metallic) * (1.0 - inputs.transmission),
+                lerp(0.25, 1.0, inputs.metallic),
+                0.25 * inputs.clearcoat,
+                glass,
+            ];
+            let total: f32 = lobes.iter().sum();
+            let lobe: usize = pick_lobe(&lobes, rand::random::<f32>() * total);
+            let (weight, wi) = match lobe {
+                0 => self.diffuse(&inputs, &wo)?,
+                1 => self.specular(&inputs, &wo)?,
+                2 => self.clearcoat(&inputs, &wo)?,
+                _ => self.transmission(&inputs, &wo, self.refract_idx)?,
+            };
+            // the diffuse and glass parts are scaled by their share of the
+            // surface, the other lobes are not
+            let share: f32 = match lobe {
+                0 | 3 => lobes[lobe],
+                _ => 1.0,
+            };
+            (weight * (share * total / lobes[lobe]), wi)
+        };
+
+        Some(ScatterRecord {
+            attenuation: weight,
+            scattered: Ray::new(rec.p, frame.to_world(&wi), r_in.time),
+        })
+    }
+
+    fn emitted(&self, _u: f32, _v: f32, _p: &Vec3) -> Vec3 {
+        Vec3::zeros()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::{CheckerTexture, ConstantTexture};
+
+    fn record(material: &dyn Material) -> HitRecord<'_> {
+        HitRecord {
+            t: 1.0,
+            u: 0.25,
+            v: 0.5,
+            p: Vec3::zeros(),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            color: None,
+            material,
+        }
+    }
+
+    // Mean attenuation of many scatterings, straight down onto the surface
+    fn albedo(material: &Principled) -> Vec3 {
+        let rec = record(material);
+        let r = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let n = 20000;
+        let mut sum = Vec3::zeros();
+        for _ in 0..n {
+            if let Some(scatter) = material.scatter(&r, &rec) {
+                sum += scatter.attenuation;
+            }
+        }
+        sum / n as f32
+    }
+
+    #[test]
+    fn energy() {
+        let white = Principled::new(ConstantTexture::new(1.0, 1.0, 1.0));
+        // subsurface flattening loses some light head on
+        for material in [
+            white.clone(),
+            white.clone().with_roughness(1.0).with_subsurface(1.0),
+            white.clone().with_sheen(1.0).with_clearcoat(1.0),
+            white.clone().with_metallic(1.0).with_roughness(0.2),
+            white.clone().with_transmission(1.0).with_roughness(0.1),
+        ] {
+            let a: Vec3 = albedo(&material);
+            for c in 0..3 {
+                assert!(a[c] > 0.7 && a[c] < 1.15, "albedo {:?}", a);
+            }
+        }
+
+        // gold-colored polished metal reflects its color head on
+        let gold = Principled::new(ConstantTexture::new(1.0, 0.8, 0.3))
+            .with_metallic(1.0)
+            .with_roughness(0.0);
+        let a: Vec3 = albedo(&gold);
+        assert!((a - Vec3::new(1.0, 0.8, 0.3)).length() < 0.02);
+    }
+
+    #[test]
+    fn textured_parameters() {
+        // metallic on the odd checker squares only
+        let checker = CheckerTexture::new(
+            ConstantTexture::new(1.0, 1.0, 1.0),
+            ConstantTexture::new(0.0, 0.0, 0.0),
+        );
+        let material = Principled::new(ConstantTexture::new(0.5, 0.5, 0.5)).with_metallic(checker);
+        let rec = record(&material);
+        let metallic: f32 = material.metallic.value_at(&rec);
+        let far = HitRecord {
+            p: Vec3::new(1.0, 1.0, 1.0),
+            ..record(&material)
+        };
+        assert!(metallic == 0.0 || metallic == 1.0);
+        assert_eq!(material.metallic.value_at(&far), 1.0 - metallic);
+        assert_eq!(material.roughness.value_at(&rec), 0.5);
+    }
+
+    #[test]
+    fn lobe_pick() {
+        let lobes: [f32; 4] = [0.5, 0.25, 0.0, 0.0];
+        assert_eq!(pick_lobe(&lobes, 0.0), 0);
+        assert_eq!(pick_lobe(&lobes, 0.6), 1);
+        // past the end, as rounding can leave it, and never a lobe that
+        // can't be picked
+        assert_eq!(pick_lobe(&lobes, 0.75), 1);
+        assert_eq!(pick_lobe(&lobes, 0.8), 1);
+        assert_eq!(pick_lobe(&[0.0, 0.25, 0.0, 0.5], 0.0), 1);
+    }
+}
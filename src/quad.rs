@@ -0,0 +1,248 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use rand;
+use std::f32::consts::PI;
+
+// Part of the plane spanned by the two edges that is actually filled
+pub enum QuadShape {
+    Parallelogram, // corner + a * u + b * v, 0 <= a, b <= 1
+    Triangle,      // corner, corner + u, corner + v
+    Disk,          // ellipse around the corner, with `u` and `v` as half axes
+}
+
+// Flat shape in any orientation, given by a corner (the center for a disk)
+// and two edge vectors. The normal is `u x v`.
+pub struct Quad<M: Material> {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub shape: QuadShape,
+    pub normal: Vec3,
+    d: f32,  // plane equation: normal . p = d
+    w: Vec3, // to get the plane coordinates of a point
+    pub material: M,
+}
+
+impl<M: Material> Quad<M> {
+    pub fn with_shape(q: Vec3, u: Vec3, v: Vec3, shape: QuadShape, material: M) -> Self {
+        let n: Vec3 = u.cross(&v);
+        let normal: Vec3 = n.unit_vector();
+        Quad {
+            q,
+            u,
+            v,
+            shape,
+            normal,
+            d: normal.dot(&q),
+            w: n / n.dot(&n),
+            material,
+        }
+    }
+
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: M) -> Self {
+        Quad::with_shape(q, u, v, QuadShape::Parallelogram, material)
+    }
+
+    pub fn triangle(q: Vec3, u: Vec3, v: Vec3, material: M) -> Self {
+        Quad::with_shape(q, u, v, QuadShape::Triangle, material)
+    }
+
+    pub fn disk(center: Vec3, u: Vec3, v: Vec3, material: M) -> Self {
+        Quad::with_shape(center, u, v, QuadShape::Disk, material)
+    }
+
+    pub fn area(&self) -> f32 {
+        let parallelogram: f32 = self.u.cross(&self.v).length();
+        match self.shape {
+            QuadShape::Parallelogram => parallelogram,
+            QuadShape::Triangle => 0.5 * parallelogram,
+            QuadShape::Disk => PI * parallelogram,
+        }
+    }
+
+    // Point spread uniformly over the surface, e.g. to aim shadow rays at a
+    // light
+    pub fn random_point(&self) -> Vec3 {
+        let (r1, r2) = (rand::random::<f32>(), rand::random::<f32>());
+        let (a, b) = match self.shape {
+            QuadShape::Parallelogram => (r1, r2),
+            // fold the other half of the parallelogram back onto the triangle
+            QuadShape::Triangle if r1 + r2 > 1.0 => (1.0 - r1, 1.0 - r2),
+            QuadShape::Triangle => (r1, r2),
+            QuadShape::Disk => {
+                let (s, c) = (2.0 * PI * r2).sin_cos();
+                (r1.sqrt() * c, r1.sqrt() * s)
+            }
+        };
+        self.q + a * self.u + b * self.v
+    }
+
+    // Texture coordinates of the plane coordinates (a, b), `None` outside
+    fn surface_uv(&self, a: f32, b: f32) -> Option<(f32, f32)> {
+        match self.shape {
+            QuadShape::Parallelogram => {
+                if (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b) {
+                    Some((a, b))
+                } else {
+                    None
+                }
+            }
+            QuadShape::Triangle => {
+                if a >= 0.0 && b >= 0.0 && a + b <= 1.0 {
+                    Some((a, b))
+                } else {
+                    None
+                }
+            }
+            QuadShape::Disk => {
+                let dist2: f32 = a * a + b * b;
+                if dist2 <= 1.0 {
+                    Some(((b.atan2(a) + PI) / (2.0 * PI), dist2.sqrt()))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl<M: Material> Hitable for Quad<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let denom: f32 = self.normal.dot(&r.direction);
+        // parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+        let t: f32 = (self.d - self.normal.dot(&r.origin)) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let p: Vec3 = r.point_at_parameter(t);
+        let planar: Vec3 = p - self.q;
+        let a: f32 = self.w.dot(&planar.cross(&self.v));
+        let b: f32 = self.w.dot(&self.u.cross(&planar));
+        let (u, v) = self.surface_uv(a, b)?;
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p,
+            normal: self.normal,
+            color: None,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let (mut min, mut max) = match self.shape {
+            QuadShape::Disk => {
+                let half = Vec3::new(
+                    (self.u.x * self.u.x + self.v.x * self.v.x).sqrt(),
+                    (self.u.y * self.u.y + self.v.y * self.v.y).sqrt(),
+                    (self.u.z * self.u.z + self.v.z * self.v.z).sqrt(),
+                );
+                (self.q - half, self.q + half)
+            }
+            _ => {
+                let corners = [
+                    self.q,
+                    self.q + self.u,
+                    self.q + self.v,
+                    self.q + self.u + self.v,
+                ];
+                let count = if let QuadShape::Triangle = self.shape {
+                    3
+                } else {
+                    4
+                };
+                let mut min = corners[0];
+                let mut max = corners[0];
+                for p in &corners[1..count] {
+                    for c in 0..3 {
+                        min[c] = min[c].min(p[c]);
+                        max[c] = max[c].max(p[c]);
+                    }
+                }
+                (min, max)
+            }
+        };
+        // flat along an axis when the shape is axis aligned
+        let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+        min -= pad;
+        max += pad;
+        Some(AABB::new(min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::DiffuseLight;
+    use crate::texture::ConstantTexture;
+
+    fn light() -> DiffuseLight<ConstantTexture> {
+        DiffuseLight::new(ConstantTexture::new(4.0, 4.0, 4.0))
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn tilted_panel() {
+        // 2 x 1 panel leaning back at 45 degrees, facing +z and up
+        let s = 0.5_f32.sqrt();
+        let quad = Quad::new(
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, s, -s),
+            light(),
+        );
+        assert!((quad.area() - 2.0).abs() < 1e-5);
+        assert_near(quad.normal, Vec3::new(0.0, s, s));
+
+        let r = Ray::new(Vec3::new(0.5, 0.25, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = quad.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_near(rec.p, Vec3::new(0.5, 0.25, -0.25));
+        assert!((rec.u - 0.75).abs() < 1e-4 && (rec.v - 0.25 / s).abs() < 1e-4);
+
+        let bbox = quad.bounding_box(0.0, 1.0).unwrap();
+        for _ in 0..100 {
+            let p = quad.random_point();
+            assert!((quad.normal.dot(&(p - quad.q))).abs() < 1e-5);
+            for c in 0..3 {
+                assert!(bbox.min[c] <= p[c] && p[c] <= bbox.max[c]);
+            }
+        }
+    }
+
+    #[test]
+    fn triangle_and_disk() {
+        let x = Vec3::new(1.0, 0.0, 0.0);
+        let z = Vec3::new(0.0, 0.0, -1.0);
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let triangle = Quad::triangle(Vec3::zeros(), x, z, light());
+        assert!((triangle.area() - 0.5).abs() < 1e-5);
+        let inside = Ray::new(Vec3::new(0.2, 1.0, -0.2), down, 0.0);
+        let outside = Ray::new(Vec3::new(0.6, 1.0, -0.6), down, 0.0);
+        assert!(triangle.hit(&inside, 0.001, f32::MAX).is_some());
+        assert!(triangle.hit(&outside, 0.001, f32::MAX).is_none());
+        for _ in 0..100 {
+            let p = triangle.random_point();
+            assert!(p.x >= 0.0 && p.z <= 0.0 && p.x - p.z <= 1.0 + 1e-5);
+        }
+
+        let disk = Quad::disk(Vec3::zeros(), 2.0 * x, z, light());
+        assert!((disk.area() - 2.0 * PI).abs() < 1e-4);
+        let r = Ray::new(Vec3::new(1.9, 1.0, 0.0), down, 0.0);
+        assert!(disk.hit(&r, 0.001, f32::MAX).is_some());
+        let r = Ray::new(Vec3::new(1.5, 1.0, -0.9), down, 0.0);
+        assert!(disk.hit(&r, 0.001, f32::MAX).is_none());
+        let bbox = disk.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.max, Vec3::new(2.0001, 0.0001, 1.0001));
+    }
+}
@@ -0,0 +1,231 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::Vec3;
+
+// Surface where, relative to `center`,
+//   a x^2 + b y^2 + c z^2 + d xy + e yz + f xz + g x + h y + i z + j = 0
+// optionally clipped to a box. Without a clip box most quadrics are
+// unbounded, which the BVHs handle by testing them against every ray.
+pub struct Quadric<M: Material> {
+    pub center: Vec3,
+    pub coefs: [f32; 10], // a to j
+    pub clip: Option<AABB>,
+    bbox: Option<AABB>,
+    pub material: M,
+}
+
+impl<M: Material> Quadric<M> {
+    pub fn new(center: Vec3, coefs: [f32; 10], material: M) -> Self {
+        Quadric {
+            center,
+            coefs,
+            clip: None,
+            bbox: None,
+            material,
+        }
+    }
+
+    // x^2 / rx^2 + y^2 / ry^2 + z^2 / rz^2 = 1
+    pub fn ellipsoid(center: Vec3, radii: Vec3, material: M) -> Self {
+        let inv = |r: f32| 1.0 / (r * r);
+        let mut quadric = Quadric::new(
+            center,
+            [
+                inv(radii.x),
+                inv(radii.y),
+                inv(radii.z),
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                -1.0,
+            ],
+            material,
+        );
+        quadric.bbox = Some(AABB::new(center - radii, center + radii));
+        quadric
+    }
+
+    // Bowl opening along +y, `radius` wide at `height`:
+    // y = height (x^2 + z^2) / radius^2
+    pub fn paraboloid(center: Vec3, radius: f32, height: f32, material: M) -> Self {
+        let k: f32 = height / (radius * radius);
+        Quadric::new(
+            center,
+            [k, 0.0, k, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0],
+            material,
+        )
+    }
+
+    // Hyperboloid around the y axis, x^2 / rx^2 - y^2 / ry^2 + z^2 / rz^2 = 1
+    // with one sheet (a cooling tower), or = -1 with two
+    pub fn hyperboloid(center: Vec3, radii: Vec3, one_sheet: bool, material: M) -> Self {
+        let inv = |r: f32| 1.0 / (r * r);
+        let j: f32 = if one_sheet { -1.0 } else { 1.0 };
+        Quadric::new(
+            center,
+            [
+                inv(radii.x),
+                -inv(radii.y),
+                inv(radii.z),
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                0.0,
+                j,
+            ],
+            material,
+        )
+    }
+
+    // Keeps only the part of the surface inside `clip`, in world space
+    pub fn with_clip(mut self, clip: AABB) -> Self {
+        self.bbox = Some(match self.bbox {
+            Some(bbox) => bbox.intersection(&clip),
+            None => clip,
+        });
+        self.clip = Some(clip);
+        self
+    }
+
+    fn inside_clip(&self, p: &Vec3) -> bool {
+        match &self.clip {
+            Some(clip) => (0..3).all(|a| p[a] >= clip.min[a] && p[a] <= clip.max[a]),
+            None => true,
+        }
+    }
+
+    // The quadratic terms alone
+    fn quadratic(&self, u: &Vec3, v: &Vec3) -> f32 {
+        let [a, b, c, d, e, f, ..] = self.coefs;
+        a * u.x * v.x
+            + b * u.y * v.y
+            + c * u.z * v.z
+            + 0.5 * d * (u.x * v.y + u.y * v.x)
+            + 0.5 * e * (u.y * v.z + u.z * v.y)
+            + 0.5 * f * (u.x * v.z + u.z * v.x)
+    }
+
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        let [a, b, c, d, e, f, g, h, i, _] = self.coefs;
+        Vec3::new(
+            2.0 * a * p.x + d * p.y + f * p.z + g,
+            2.0 * b * p.y + d * p.x + e * p.z + h,
+            2.0 * c * p.z + e * p.y + f * p.x + i,
+        )
+    }
+}
+
+impl<M: Material> Hitable for Quadric<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let [_, _, _, _, _, _, g, h, i, j] = self.coefs;
+        let linear = Vec3::new(g, h, i);
+        let o: Vec3 = r.origin - self.center;
+        let d: Vec3 = r.direction;
+        // a t^2 + 2 b t + c = 0
+        let qa: f32 = self.quadratic(&d, &d);
+        let qb: f32 = self.quadratic(&o, &d) + 0.5 * linear.dot(&d);
+        let qc: f32 = self.quadratic(&o, &o) + linear.dot(&o) + j;
+
+        let roots: Vec<f32> = if qa.abs() < 1e-8 {
+            if qb == 0.0 {
+                vec![]
+            } else {
+                vec![-qc / (2.0 * qb)]
+            }
+        } else {
+            let discriminant: f32 = qb * qb - qa * qc;
+            if discriminant <= 0.0 {
+                return None;
+            }
+            let t1: f32 = (-qb - discriminant.sqrt()) / qa;
+            let t2: f32 = (-qb + discriminant.sqrt()) / qa;
+            vec![t1.min(t2), t1.max(t2)]
+        };
+
+        roots.into_iter().find_map(|t| {
+            if t <= t_min || t >= t_max {
+                return None;
+            }
+            let p: Vec3 = r.point_at_parameter(t);
+            if !self.inside_clip(&p) {
+                return None;
+            }
+            let local: Vec3 = p - self.center;
+            let (u, v) = get_sphere_uv(&local.unit_vector());
+            Some(HitRecord {
+                t,
+                u,
+                v,
+                p,
+                normal: self.gradient(&local).unit_vector(),
+                color: None,
+                material: &self.material,
+            })
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn ellipsoid() {
+        let e = Quadric::ellipsoid(Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0), white());
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = e.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        let bbox = e.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.max, Vec3::new(3.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn clipped_surfaces() {
+        let clip = AABB::new(Vec3::new(-2.0, 0.0, -2.0), Vec3::new(2.0, 1.0, 2.0));
+        let bowl = Quadric::paraboloid(Vec3::zeros(), 1.0, 1.0, white());
+        assert!(bowl.bounding_box(0.0, 1.0).is_none());
+        let bowl = bowl.with_clip(clip);
+
+        // down into the bowl, onto its inside
+        let r = Ray::new(Vec3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = bowl.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.75).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(1.0, -1.0, 0.0).unit_vector());
+        // beside the rim, where the unclipped bowl would go on
+        let r = Ray::new(Vec3::new(1.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        assert!(bowl.hit(&r, 0.001, f32::MAX).is_none());
+
+        // through the waist of a cooling tower, then out of the far side
+        let tower = Quadric::hyperboloid(Vec3::zeros(), Vec3::new(1.0, 1.0, 1.0), true, white())
+            .with_clip(clip);
+        let r = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        let rec = tower.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-4);
+        let rec = tower.hit(&r, rec.t + 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(1.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,146 @@
+use crate::matrix::Matrix4;
+use crate::vec3::Vec3;
+
+use std::ops::Mul;
+
+// Unit quaternion representing a rotation
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Self {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Self {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // Rotation of `angle` degrees around `axis`, matching `Matrix4::rotation`
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let a: Vec3 = axis.unit_vector();
+        let (s, c) = (0.5 * angle.to_radians()).sin_cos();
+        Quaternion::new(c, a.x * s, a.y * s, a.z * s)
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> f32 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let len: f32 = self.dot(self).sqrt();
+        Quaternion::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    // Angle in radians of the rotation taking `self` to `other`
+    pub fn angle_to(&self, other: &Quaternion) -> f32 {
+        2.0 * self.dot(other).abs().min(1.0).acos()
+    }
+
+    // Spherical linear interpolation along the shortest arc
+    pub fn slerp(&self, other: &Quaternion, t: f32) -> Quaternion {
+        let mut cos_theta: f32 = self.dot(other);
+        let mut other: Quaternion = *other;
+        if cos_theta < 0.0 {
+            other = Quaternion::new(-other.w, -other.x, -other.y, -other.z);
+            cos_theta = -cos_theta;
+        }
+        let (a, b) = if cos_theta > 0.9995 {
+            // nearly the same rotation, fall back to linear interpolation
+            (1.0 - t, t)
+        } else {
+            let theta: f32 = cos_theta.acos();
+            let sin_theta: f32 = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+        Quaternion::new(
+            a * self.w + b * other.w,
+            a * self.x + b * other.x,
+            a * self.y + b * other.y,
+            a * self.z + b * other.z,
+        )
+        .normalize()
+    }
+
+    pub fn to_matrix(&self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = *self;
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+// Composition: `a * b` rotates by `b` first, then by `a`
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, o: Quaternion) -> Self::Output {
+        Quaternion::new(
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: &Matrix4, b: &Matrix4) {
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((a.m[i][j] - b.m[i][j]).abs() < 1e-5, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn same_as_matrix_rotation() {
+        let axis = Vec3::new(1.0, -2.0, 0.5);
+        let q = Quaternion::from_axis_angle(axis, 70.0);
+        assert_near(&q.to_matrix(), &Matrix4::rotation(axis, 70.0));
+
+        let r = Quaternion::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 25.0);
+        assert_near(
+            &(q * r).to_matrix(),
+            &(Matrix4::rotation(axis, 70.0) * Matrix4::rotation_y(25.0)),
+        );
+    }
+
+    #[test]
+    fn slerp_halfway() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+        let a = Quaternion::from_axis_angle(axis, 10.0);
+        let b = Quaternion::from_axis_angle(axis, 130.0);
+        assert_near(&a.slerp(&b, 0.5).to_matrix(), &Matrix4::rotation_z(70.0));
+        assert!((a.angle_to(&b) - 120.0_f32.to_radians()).abs() < 1e-4);
+    }
+}
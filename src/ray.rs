@@ -5,6 +5,8 @@ pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub time: f32,
+    // in nanometers, once a dispersive material has picked one for the path
+    pub wavelength: Option<f32>,
 }
 
 impl Ray {
@@ -13,6 +15,7 @@ impl Ray {
             origin,
             direction,
             time,
+            wavelength: None,
         }
     }
 
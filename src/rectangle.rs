@@ -35,14 +35,15 @@ impl<M: Material> Rectangle<M> {
 }
 
 impl<M: Material> Hitable for Rectangle<M> {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let (a_axis, b_axis, k_axis) = match &self.plane {
             Plane::XY => (0, 1, 2),
             Plane::YZ => (1, 2, 0),
             Plane::ZX => (2, 0, 1),
         };
         let t: f32 = (self.k - r.origin[k_axis]) / r.direction[k_axis];
-        if t < t_min || t > t_max {
+        // also rejects NaN, when the origin lies in the plane of a parallel ray
+        if !(t_min..=t_max).contains(&t) {
             None
         } else {
             let a: f32 = r.origin[a_axis] + t * r.direction[a_axis];
@@ -65,6 +66,7 @@ impl<M: Material> Hitable for Rectangle<M> {
                     v,
                     p,
                     normal,
+                    color: None,
                     material: &self.material,
                 })
             }
@@ -72,10 +74,20 @@ impl<M: Material> Hitable for Rectangle<M> {
     }
 
     fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
-        let bbox = AABB::new(
-            Vec3::new(self.a0, self.a0, self.k - 0.0001),
-            Vec3::new(self.b1, self.b1, self.k + 0.0001),
-        );
-        Some(bbox)
+        let (a_axis, b_axis, k_axis) = match &self.plane {
+            Plane::XY => (0, 1, 2),
+            Plane::YZ => (1, 2, 0),
+            Plane::ZX => (2, 0, 1),
+        };
+        // pad the box a little along `k` so that it doesn't have zero width
+        let mut min = Vec3::zeros();
+        let mut max = Vec3::zeros();
+        min[a_axis] = self.a0;
+        max[a_axis] = self.a1;
+        min[b_axis] = self.b0;
+        max[b_axis] = self.b1;
+        min[k_axis] = self.k - 0.0001;
+        max[k_axis] = self.k + 0.0001;
+        Some(AABB::new(min, max))
     }
 }
@@ -4,10 +4,12 @@ use crate::hitable::Hitable;
 use crate::hitable_list::HitableList;
 use crate::material::*;
 use crate::medium::ConstantMedium;
+use crate::principled::Principled;
 use crate::rectangle::*;
 use crate::sphere::{MovingSphere, Sphere};
 use crate::texture::*;
-use crate::translate::{Rotate, Translate};
+use crate::thin_film::ThinFilm;
+use crate::transform::Transform;
 use crate::vec3::Vec3;
 
 use image;
@@ -266,6 +268,92 @@ pub fn simple_light() -> HitableList {
     HitableList { hitables }
 }
 
+// One principled material in place of the Lambertian, Metal and Dielectric
+// mix: a row of spheres, each showing off a different parameter
+pub fn principled_spheres() -> HitableList {
+    let checker = CheckerTexture::new(
+        ConstantTexture::new(0.2, 0.3, 0.1),
+        ConstantTexture::new(0.9, 0.9, 0.9),
+    );
+    let red = ConstantTexture::new(0.8, 0.1, 0.1);
+    let materials: Vec<Principled> = vec![
+        Principled::new(red.clone()).with_subsurface(1.0),
+        Principled::new(red.clone())
+            .with_sheen(1.0)
+            .with_roughness(1.0),
+        Principled::new(red).with_clearcoat(1.0).with_roughness(0.3),
+        Principled::new(ConstantTexture::new(1.0, 0.8, 0.3))
+            .with_metallic(1.0)
+            .with_roughness(0.2),
+        Principled::new(ConstantTexture::new(0.9, 1.0, 0.9))
+            .with_transmission(1.0)
+            .with_roughness(0.05),
+    ];
+
+    let mut hitables: Vec<Box<dyn Hitable>> = vec![Box::new(Sphere::new(
+        Vec3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Principled::new(checker.clone()).with_roughness(checker),
+    ))];
+    for (i, material) in materials.into_iter().enumerate() {
+        hitables.push(Box::new(Sphere::new(
+            Vec3::new(-5.0 + 2.5 * i as f32, 1.0, 0.0),
+            1.0,
+            material,
+        )));
+    }
+    hitables.push(Box::new(Rectangle::new(
+        Plane::ZX,
+        -3.0,
+        3.0,
+        -4.0,
+        4.0,
+        6.0,
+        DiffuseLight::new(ConstantTexture::new(4.0, 4.0, 4.0)),
+    )));
+
+    HitableList { hitables }
+}
+
+// Soap bubble with swirling thickness next to oxidized copper
+pub fn soap_bubbles() -> HitableList {
+    let hitables: Vec<Box<dyn Hitable>> = vec![
+        Box::new(Sphere::new(
+            Vec3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            Lambertian::new(CheckerTexture::new(
+                ConstantTexture::new(0.2, 0.3, 0.1),
+                ConstantTexture::new(0.9, 0.9, 0.9),
+            )),
+        )),
+        Box::new(Sphere::new(
+            Vec3::new(-1.5, 2.0, 0.0),
+            1.5,
+            ThinFilm::bubble(NoiseTexture::new(2.0)).with_thickness_range(200.0, 800.0),
+        )),
+        Box::new(Sphere::new(
+            Vec3::new(1.5, 1.0, 0.0),
+            1.0,
+            ThinFilm::over_conductor(
+                Conductor::copper(ConstantTexture::new(0.2, 0.2, 0.2)),
+                2.0,
+                ConstantTexture::new(0.15, 0.15, 0.15),
+            ),
+        )),
+        Box::new(Rectangle::new(
+            Plane::ZX,
+            -3.0,
+            3.0,
+            -4.0,
+            4.0,
+            6.0,
+            DiffuseLight::new(ConstantTexture::new(4.0, 4.0, 4.0)),
+        )),
+    ];
+
+    HitableList { hitables }
+}
+
 pub fn cornell_box() -> HitableList {
     let red = Lambertian::new(ConstantTexture::new(0.65, 0.05, 0.05));
     let white = Lambertian::new(ConstantTexture::new(0.73, 0.73, 0.73));
@@ -334,28 +422,20 @@ pub fn blocks() -> HitableList {
     let green = Lambertian::new(ConstantTexture::new(0.12, 0.45, 0.15));
     let light = DiffuseLight::new(ConstantTexture::new(7.0, 7.0, 7.0));
 
-    let box1 = Translate::new(
-        Rotate::new(
-            Block::new(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(165.0, 165.0, 165.0),
-                white.clone(),
-            ),
-            -18.0,
-        ),
-        Vec3::new(130.0, 0.0, 65.0),
-    );
-    let box2 = Translate::new(
-        Rotate::new(
-            Block::new(
-                Vec3::new(0.0, 0.0, 0.0),
-                Vec3::new(165.0, 330.0, 165.0),
-                white.clone(),
-            ),
-            15.0,
-        ),
-        Vec3::new(265.0, 0.0, 295.0),
-    );
+    let box1 = Transform::new(Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 165.0, 165.0),
+        white.clone(),
+    ))
+    .rotate_y(-18.0)
+    .translate(Vec3::new(130.0, 0.0, 65.0));
+    let box2 = Transform::new(Block::new(
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(165.0, 330.0, 165.0),
+        white.clone(),
+    ))
+    .rotate_y(15.0)
+    .translate(Vec3::new(265.0, 0.0, 295.0));
     let hitables: Vec<Box<dyn Hitable>> = vec![
         Box::new(FlipNormals::new(Rectangle::new(
             Plane::YZ,
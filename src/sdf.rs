@@ -0,0 +1,307 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::get_sphere_uv;
+use crate::vec3::Vec3;
+
+// Signed distance to a surface: negative inside, positive outside, and never
+// more than the true distance so that sphere tracing cannot step through it
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: &Vec3) -> f32;
+}
+
+impl<F: Fn(&Vec3) -> f32 + Send + Sync> Sdf for F {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self(p)
+    }
+}
+
+// Composable distance functions
+pub enum SdfNode {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    Cuboid {
+        center: Vec3,
+        half_size: Vec3,
+    },
+    Torus {
+        center: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Union(Box<SdfNode>, Box<SdfNode>),
+    SmoothUnion {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        k: f32, // how far the blend reaches
+    },
+    // turns space around the y axis by `rate` radians per unit of height
+    Twist {
+        node: Box<SdfNode>,
+        rate: f32,
+    },
+    // copies of `node` every `period` along each axis, 0 to not repeat
+    Repeat {
+        node: Box<SdfNode>,
+        period: Vec3,
+    },
+}
+
+impl SdfNode {
+    pub fn sphere(center: Vec3, radius: f32) -> Self {
+        SdfNode::Sphere { center, radius }
+    }
+
+    pub fn cuboid(center: Vec3, half_size: Vec3) -> Self {
+        SdfNode::Cuboid { center, half_size }
+    }
+
+    // Ring around the y axis, like `torus::Torus`
+    pub fn torus(center: Vec3, major_radius: f32, minor_radius: f32) -> Self {
+        SdfNode::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn union(self, other: SdfNode) -> Self {
+        SdfNode::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: SdfNode, k: f32) -> Self {
+        SdfNode::SmoothUnion {
+            a: Box::new(self),
+            b: Box::new(other),
+            k,
+        }
+    }
+
+    // Twisting stretches distances, so the surface needs a `step_scale`
+    // below 1 in `SdfHitable` to be traced safely
+    pub fn twist(self, rate: f32) -> Self {
+        SdfNode::Twist {
+            node: Box::new(self),
+            rate,
+        }
+    }
+
+    pub fn repeat(self, period: Vec3) -> Self {
+        SdfNode::Repeat {
+            node: Box::new(self),
+            period,
+        }
+    }
+}
+
+impl Sdf for SdfNode {
+    fn distance(&self, p: &Vec3) -> f32 {
+        match self {
+            SdfNode::Sphere { center, radius } => (*p - *center).length() - radius,
+            SdfNode::Cuboid { center, half_size } => {
+                let d: Vec3 = *p - *center;
+                let q = Vec3::new(
+                    d.x.abs() - half_size.x,
+                    d.y.abs() - half_size.y,
+                    d.z.abs() - half_size.z,
+                );
+                let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+                outside.length() + q.x.max(q.y).max(q.z).min(0.0)
+            }
+            SdfNode::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let d: Vec3 = *p - *center;
+                let ring: f32 = (d.x * d.x + d.z * d.z).sqrt() - major_radius;
+                (ring * ring + d.y * d.y).sqrt() - minor_radius
+            }
+            SdfNode::Union(a, b) => a.distance(p).min(b.distance(p)),
+            SdfNode::SmoothUnion { a, b, k } => {
+                // polynomial smooth minimum
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h: f32 = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+                db + (da - db) * h - k * h * (1.0 - h)
+            }
+            SdfNode::Twist { node, rate } => {
+                let (s, c) = (-rate * p.y).sin_cos();
+                node.distance(&Vec3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z))
+            }
+            SdfNode::Repeat { node, period } => {
+                let mut q: Vec3 = *p;
+                for a in 0..3 {
+                    if period[a] > 0.0 {
+                        q[a] -= period[a] * (q[a] / period[a]).round();
+                    }
+                }
+                node.distance(&q)
+            }
+        }
+    }
+}
+
+// Surface where `sdf` is zero, found by sphere tracing: every step moves
+// along the ray by the distance to the surface, which cannot overshoot.
+// Marching is limited to `bbox`, which also serves as the bounding box, so
+// it has to enclose the part of the surface that should be seen.
+pub struct SdfHitable<S: Sdf, M: Material> {
+    pub sdf: S,
+    pub bbox: AABB,
+    pub material: M,
+    pub epsilon: f32,    // distance counted as being on the surface
+    pub max_steps: u32,  // give up after this many steps
+    pub step_scale: f32, // below 1 for functions that overestimate distance
+}
+
+impl<S: Sdf, M: Material> SdfHitable<S, M> {
+    pub fn new(sdf: S, bbox: AABB, material: M) -> Self {
+        SdfHitable {
+            sdf,
+            bbox,
+            material,
+            epsilon: 1e-4,
+            max_steps: 256,
+            step_scale: 1.0,
+        }
+    }
+
+    pub fn with_epsilon(mut self, epsilon: f32) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f32) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    // Central differences of the distance
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let h: f32 = self.epsilon.max(1e-4);
+        let mut gradient = Vec3::zeros();
+        for a in 0..3 {
+            let mut offset = Vec3::zeros();
+            offset[a] = h;
+            gradient[a] = self.sdf.distance(&(*p + offset)) - self.sdf.distance(&(*p - offset));
+        }
+        gradient.unit_vector()
+    }
+}
+
+impl<S: Sdf, M: Material> Hitable for SdfHitable<S, M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (start, end) = self.bbox.hit_interval(r, t_min, t_max)?;
+        let speed: f32 = r.direction.length();
+        let mut t: f32 = start;
+        // rays starting inside (e.g. refracted ones) march towards the
+        // surface from below zero
+        let side: f32 = self.sdf.distance(&r.point_at_parameter(t)).signum();
+        for _ in 0..self.max_steps {
+            let p: Vec3 = r.point_at_parameter(t);
+            let dist: f32 = side * self.sdf.distance(&p);
+            if dist < self.epsilon {
+                if t <= t_min {
+                    // still on the surface the ray is leaving
+                    t += 2.0 * self.epsilon / speed;
+                    continue;
+                }
+                let normal: Vec3 = self.normal(&p);
+                let (u, v) = get_sphere_uv(&normal);
+                return Some(HitRecord {
+                    t,
+                    u,
+                    v,
+                    p,
+                    normal,
+                    color: None,
+                    material: &self.material,
+                });
+            }
+            t += self.step_scale * dist / speed;
+            if t >= end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(self.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::texture::ConstantTexture;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn cube(half: f32) -> AABB {
+        AABB::new(Vec3::new(-half, -half, -half), Vec3::new(half, half, half))
+    }
+
+    #[test]
+    fn same_as_sphere() {
+        let center = Vec3::new(0.2, -0.1, 0.3);
+        let sphere = Sphere::new(center, 0.8, white());
+        let sdf = SdfHitable::new(
+            move |p: &Vec3| (*p - center).length() - 0.8,
+            cube(2.0),
+            white(),
+        );
+        for i in 0..20 {
+            let target = Vec3::new(0.1 * i as f32 - 1.0, 0.05 * i as f32 - 0.5, 0.0);
+            let origin = Vec3::new(0.0, 0.0, 5.0);
+            let r = Ray::new(origin, 3.0 * (target - origin), 0.0);
+            let a = sphere.hit(&r, 0.001, f32::MAX);
+            let b = sdf.hit(&r, 0.001, f32::MAX);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                assert!((a.t - b.t).abs() < 1e-3);
+                assert!((a.normal - b.normal).length() < 1e-2);
+            }
+        }
+
+        // from inside, out through the far side
+        let r = Ray::new(center, Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = sdf.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 0.8).abs() < 1e-3);
+        assert!((rec.normal - Vec3::new(0.0, 0.0, -1.0)).length() < 1e-2);
+    }
+
+    #[test]
+    fn node_tree() {
+        let shape = SdfNode::cuboid(Vec3::zeros(), Vec3::new(0.5, 0.5, 0.5))
+            .smooth_union(SdfNode::sphere(Vec3::new(0.0, 0.8, 0.0), 0.4), 0.2)
+            .union(SdfNode::torus(Vec3::zeros(), 1.2, 0.1));
+        assert!((shape.distance(&Vec3::new(1.0, 0.0, 0.0)) - 0.1).abs() < 1e-5);
+        assert!(shape.distance(&Vec3::new(0.0, 1.1, 0.0)) < 0.0);
+        assert!((shape.distance(&Vec3::new(0.0, 0.0, -2.0)) - 0.7).abs() < 1e-5);
+
+        let twisted = SdfNode::cuboid(Vec3::zeros(), Vec3::new(0.5, 2.0, 0.1)).twist(0.5);
+        let grid = SdfNode::sphere(Vec3::zeros(), 0.25).repeat(Vec3::new(1.0, 0.0, 1.0));
+        assert!((grid.distance(&Vec3::new(3.0, 0.0, -7.5)) - 0.25).abs() < 1e-5);
+
+        let hitable = SdfHitable::new(twisted, cube(3.0), white()).with_step_scale(0.6);
+        let r = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = hitable.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 4.9).abs() < 1e-3);
+        // nothing left to hit beyond the box
+        let r = Ray::new(Vec3::new(2.5, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(hitable.hit(&r, 0.001, f32::MAX).is_none());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::vec3::Vec3;
+
+// Visible range sampled for dispersion, in nanometers
+pub const WAVELENGTH_MIN: f32 = 380.0;
+pub const WAVELENGTH_MAX: f32 = 780.0;
+
+pub fn sample_wavelength() -> f32 {
+    WAVELENGTH_MIN + rand::random::<f32>() * (WAVELENGTH_MAX - WAVELENGTH_MIN)
+}
+
+// Piecewise Gaussian with different widths on each side of the peak
+fn lobe(wavelength: f32, peak: f32, below: f32, above: f32) -> f32 {
+    let width: f32 = if wavelength < peak { below } else { above };
+    let x: f32 = (wavelength - peak) / width;
+    (-0.5 * x * x).exp()
+}
+
+// Linear RGB weight of a single wavelength. Channels are scaled so that
+// they average to one over wavelengths sampled uniformly between
+// `WAVELENGTH_MIN` and `WAVELENGTH_MAX`, which keeps white light white.
+pub fn wavelength_to_rgb(wavelength: f32) -> Vec3 {
+    // CIE 1931 matching functions (Wyman, Sloan and Shirley's fit)
+    let l: f32 = wavelength;
+    let x: f32 = 1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(l, 501.1, 20.4, 26.2);
+    let y: f32 = 0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1);
+    let z: f32 = 1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8);
+    // to linear sRGB, leaving out the colors it cannot show
+    let r: f32 = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g: f32 = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b: f32 = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    Vec3::new(
+        2.27037 * r.max(0.0),
+        3.46661 * g.max(0.0),
+        3.65978 * b.max(0.0),
+    )
+}
+
+// Index of refraction varying with the wavelength
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dispersion {
+    // n = a + b / l^2, with l in micrometers
+    Cauchy { a: f32, b: f32 },
+    // n^2 = 1 + sum of b l^2 / (l^2 - c), with l in micrometers
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier {
+            b: [1.039612, 0.23179234, 1.0104695],
+            c: [0.006000699, 0.020017914, 103.56065],
+        }
+    }
+
+    pub fn fused_silica() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.6961663, 0.4079426, 0.8974794],
+            c: [0.004679148, 0.013512063, 97.934],
+        }
+    }
+
+    pub fn diamond() -> Self {
+        Dispersion::Sellmeier {
+            b: [0.3306, 4.3356, 0.0],
+            c: [0.030625, 0.011236, 0.0],
+        }
+    }
+
+    pub fn index(&self, wavelength: f32) -> f32 {
+        let l2: f32 = (wavelength / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_stays_white() {
+        let n = 4000;
+        let mut mean = Vec3::zeros();
+        for i in 0..n {
+            let t: f32 = (i as f32 + 0.5) / n as f32;
+            mean += wavelength_to_rgb(WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN));
+        }
+        mean /= n as f32;
+        assert!((mean - Vec3::new(1.0, 1.0, 1.0)).length() < 1e-3);
+        let red: Vec3 = wavelength_to_rgb(650.0);
+        assert!(red.x > 10.0 * red.y && red.x > 10.0 * red.z);
+    }
+
+    #[test]
+    fn glass_indices() {
+        // at the sodium d line, and blue bent more than red
+        let d: f32 = 587.6;
+        assert!((Dispersion::bk7().index(d) - 1.5168).abs() < 1e-3);
+        assert!((Dispersion::fused_silica().index(d) - 1.4585).abs() < 1e-3);
+        assert!((Dispersion::diamond().index(d) - 2.417).abs() < 5e-3);
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+        assert!((cauchy.index(400.0) - 1.525).abs() < 1e-5);
+        for glass in &[Dispersion::bk7(), Dispersion::diamond(), cauchy] {
+            assert!(glass.index(450.0) > glass.index(650.0));
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::aabb::*;
-use crate::hitable::{HitRecord, Hitable};
+use crate::hitable::{HitRecord, Hitable, Interval, Solid};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::Vec3;
@@ -23,8 +23,25 @@ impl<M: Material> Sphere<M> {
     }
 }
 
+impl<M: Material> Sphere<M> {
+    fn record(&self, r: &Ray, t: f32) -> HitRecord<'_> {
+        let p: Vec3 = r.point_at_parameter(t);
+        let normal: Vec3 = (p - self.center) / self.radius;
+        let (u, v) = get_sphere_uv(&normal);
+        HitRecord {
+            t,
+            u,
+            v,
+            p,
+            normal,
+            color: None,
+            material: &self.material,
+        }
+    }
+}
+
 impl<M: Material> Hitable for Sphere<M> {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         let oc: Vec3 = r.origin - self.center;
         let a: f32 = r.direction.dot(&r.direction);
         let b: f32 = oc.dot(&r.direction);
@@ -41,17 +58,7 @@ impl<M: Material> Hitable for Sphere<M> {
             if discriminant > 0. && (b1 || b2) {
                 // temp1の方がスクリーンに近い
                 let t: f32 = if b1 { temp1 } else { temp2 };
-                let p: Vec3 = r.point_at_parameter(t);
-                let normal: Vec3 = (p - self.center) / self.radius;
-                let (u, v) = get_sphere_uv(&normal);
-                Some(HitRecord {
-                    t,
-                    u,
-                    v,
-                    p,
-                    normal,
-                    material: &self.material,
-                })
+                Some(self.record(r, t))
             } else {
                 None
             }
@@ -61,7 +68,7 @@ impl<M: Material> Hitable for Sphere<M> {
         }
     }
 
-    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
         let bbox = AABB::new(
             self.center - Vec3::new(self.radius, self.radius, self.radius),
             self.center + Vec3::new(self.radius, self.radius, self.radius),
@@ -70,6 +77,24 @@ impl<M: Material> Hitable for Sphere<M> {
     }
 }
 
+impl<M: Material> Solid for Sphere<M> {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        let oc: Vec3 = r.origin - self.center;
+        let a: f32 = r.direction.dot(&r.direction);
+        let b: f32 = oc.dot(&r.direction);
+        let c: f32 = oc.dot(&oc) - self.radius * self.radius;
+        let discriminant: f32 = b * b - a * c;
+        if discriminant > 0. {
+            vec![Interval {
+                enter: self.record(r, (-b - discriminant.sqrt()) / a),
+                exit: self.record(r, (-b + discriminant.sqrt()) / a),
+            }]
+        } else {
+            vec![]
+        }
+    }
+}
+
 pub struct MovingSphere<M: Material> {
     pub center0: Vec3,
     pub center1: Vec3,
@@ -130,6 +155,7 @@ impl<M: Material> Hitable for MovingSphere<M> {
                     v,
                     p,
                     normal,
+                    color: None,
                     material: self.material.borrow(),
                 })
             } else {
@@ -156,7 +182,8 @@ impl<M: Material> Hitable for MovingSphere<M> {
     }
 }
 
-fn get_sphere_uv(p: &Vec3) -> (f32, f32) {
+// Texture coordinates of a point on the unit sphere
+pub fn get_sphere_uv(p: &Vec3) -> (f32, f32) {
     let phi: f32 = p.z.atan2(p.x);
     let thera: f32 = p.y.asin();
     let u: f32 = 1.0 - (phi + PI) / (2.0 * PI);
@@ -0,0 +1,191 @@
+use crate::material::Material;
+use crate::mesh::{MeshData, MeshError, TriangleMesh};
+use crate::vec3::Vec3;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+fn parse_error(message: &str) -> MeshError {
+    MeshError::Parse(format!("STL: {}", message))
+}
+
+// STL stores every triangle on its own, so identical corners are merged back
+// into shared vertices
+struct Welder {
+    data: MeshData,
+    ids: HashMap<[u32; 3], u32>,
+}
+
+impl Welder {
+    fn new() -> Self {
+        Welder {
+            data: MeshData::default(),
+            ids: HashMap::new(),
+        }
+    }
+
+    fn vertex(&mut self, p: Vec3) -> u32 {
+        let positions = &mut self.data.positions;
+        *self
+            .ids
+            .entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+            .or_insert_with(|| {
+                positions.push(p);
+                positions.len() as u32 - 1
+            })
+    }
+
+    fn triangle(&mut self, v: [Vec3; 3]) {
+        let face = [self.vertex(v[0]), self.vertex(v[1]), self.vertex(v[2])];
+        self.data.indices.push(face);
+    }
+}
+
+// 80 byte header, triangle count, then 50 bytes per triangle: normal,
+// three vertices and an attribute byte count. None when the count in the
+// header is too large for any file.
+fn binary_len(count: usize) -> Option<usize> {
+    count.checked_mul(50).and_then(|n| n.checked_add(84))
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<MeshData, MeshError> {
+    if bytes.len() < 84 {
+        return Err(parse_error("file too short"));
+    }
+    let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    match binary_len(count) {
+        Some(len) if bytes.len() >= len => {}
+        _ => return Err(parse_error("unexpected end of file")),
+    }
+    let float = |pos: usize| {
+        f32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+    };
+    let point = |pos: usize| Vec3::new(float(pos), float(pos + 4), float(pos + 8));
+
+    let mut welder = Welder::new();
+    for i in 0..count {
+        let base = 84 + 50 * i + 12; // skip the facet normal
+        welder.triangle([point(base), point(base + 12), point(base + 24)]);
+    }
+    Ok(welder.data)
+}
+
+fn parse_ascii(source: &str) -> Result<MeshData, MeshError> {
+    let mut welder = Welder::new();
+    let mut corners: Vec<Vec3> = vec![];
+    for line in source.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"vertex") => {
+                if tokens.len() != 4 {
+                    return Err(parse_error(&format!("invalid vertex `{}`", line.trim())));
+                }
+                let mut v = [0.0f32; 3];
+                for (k, token) in tokens[1..].iter().enumerate() {
+                    v[k] = token
+                        .parse()
+                        .map_err(|_| parse_error(&format!("invalid number `{}`", token)))?;
+                }
+                corners.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            Some(&"endloop") => {
+                if corners.len() < 3 {
+                    return Err(parse_error("a facet needs at least 3 vertices"));
+                }
+                for i in 1..corners.len() - 1 {
+                    welder.triangle([corners[0], corners[i], corners[i + 1]]);
+                }
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+    Ok(welder.data)
+}
+
+pub fn parse_stl(bytes: &[u8]) -> Result<MeshData, MeshError> {
+    // binary files may also start with "solid", so trust the size first
+    let is_binary = bytes.len() >= 84 && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        binary_len(count) == Some(bytes.len())
+    };
+    if is_binary || !bytes.starts_with(b"solid") {
+        parse_binary(bytes)
+    } else {
+        let source =
+            std::str::from_utf8(bytes).map_err(|_| parse_error("ASCII file is not valid text"))?;
+        parse_ascii(source)
+    }
+}
+
+// Loads a binary or ASCII STL file. The facets are flat shaded.
+pub fn load_stl<P: AsRef<Path>, M: Material>(
+    path: P,
+    material: M,
+) -> Result<TriangleMesh<M>, MeshError> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| MeshError::Io(path.to_path_buf(), e))?;
+    Ok(parse_stl(&bytes)?.into_mesh(material))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary() {
+        let mut bytes = b"solid but actually binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        let triangles = [
+            [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+            [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+        ];
+        for triangle in &triangles {
+            bytes.extend_from_slice(&[0u8; 12]);
+            for x in triangle.iter().flatten() {
+                bytes.extend_from_slice(&x.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0u8; 2]);
+        }
+        let data = parse_stl(&bytes).unwrap();
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+
+        bytes.truncate(bytes.len() - 10);
+        assert!(parse_binary(&bytes).is_err());
+        // a count too large for the file, or for memory
+        bytes[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_binary(&bytes).is_err());
+        assert_eq!(binary_len(usize::MAX / 50), None);
+    }
+
+    #[test]
+    fn ascii() {
+        let source = "solid quad
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid quad
+";
+        let data = parse_stl(source.as_bytes()).unwrap();
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.positions[3], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+
+        let err = parse_stl(b"solid x\nvertex 0 0 zero\n").unwrap_err();
+        assert_eq!(err.to_string(), "STL: invalid number `zero`");
+    }
+}
@@ -0,0 +1,543 @@
+use crate::mesh::{weld, MeshData};
+use crate::vec3::Vec3;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scheme {
+    Loop,         // triangles split in four, stays a triangle mesh
+    CatmullClark, // polygons split into quads, then each quad in two triangles
+}
+
+// Faces around each edge and edges around each vertex, for polygons given
+// as vertex loops
+struct Topology {
+    edges: Vec<[u32; 2]>,
+    edge_faces: Vec<Vec<usize>>,
+    face_edges: Vec<Vec<usize>>, // edge i joins corners i and i + 1
+    vertex_edges: Vec<Vec<usize>>,
+    lookup: HashMap<(u32, u32), usize>,
+}
+
+impl Topology {
+    fn new(vertex_count: usize, faces: &[Vec<u32>]) -> Self {
+        let mut topology = Topology {
+            edges: vec![],
+            edge_faces: vec![],
+            face_edges: vec![],
+            vertex_edges: vec![vec![]; vertex_count],
+            lookup: HashMap::new(),
+        };
+        for (f, face) in faces.iter().enumerate() {
+            let mut face_edges = Vec::with_capacity(face.len());
+            for (k, &a) in face.iter().enumerate() {
+                let b: u32 = face[(k + 1) % face.len()];
+                let key = (a.min(b), a.max(b));
+                let e: usize = match topology.lookup.get(&key) {
+                    Some(&e) => e,
+                    None => {
+                        let e: usize = topology.edges.len();
+                        topology.edges.push([key.0, key.1]);
+                        topology.edge_faces.push(vec![]);
+                        topology.vertex_edges[a as usize].push(e);
+                        topology.vertex_edges[b as usize].push(e);
+                        topology.lookup.insert(key, e);
+                        e
+                    }
+                };
+                topology.edge_faces[e].push(f);
+                face_edges.push(e);
+            }
+            topology.face_edges.push(face_edges);
+        }
+        topology
+    }
+
+    fn edge(&self, a: u32, b: u32) -> usize {
+        self.lookup[&(a.min(b), a.max(b))]
+    }
+
+    fn is_boundary(&self, e: usize) -> bool {
+        self.edge_faces[e].len() != 2
+    }
+
+    fn other_end(&self, e: usize, v: usize) -> usize {
+        let [a, b] = self.edges[e];
+        if a as usize == v {
+            b as usize
+        } else {
+            a as usize
+        }
+    }
+
+    // Where a vertex on a border moves to, following the border as a cubic
+    // B-spline so that both sides of a seam agree. Corners where more than
+    // two borders meet stay in place. None inside the surface.
+    fn boundary_vertex(&self, v: usize, positions: &[Vec3]) -> Option<Vec3> {
+        let ends: Vec<usize> = self.vertex_edges[v]
+            .iter()
+            .filter(|&&e| self.is_boundary(e))
+            .map(|&e| self.other_end(e, v))
+            .collect();
+        match ends.len() {
+            0 => None,
+            2 => Some(0.75 * positions[v] + 0.125 * (positions[ends[0]] + positions[ends[1]])),
+            _ => Some(positions[v]),
+        }
+    }
+}
+
+// Linearly interpolated vertex attributes (uvs and colors) follow the
+// positions, new vertices taking the average of those they come from
+struct Attributes {
+    uvs: Vec<(f32, f32)>,
+    colors: Vec<Vec3>,
+}
+
+impl Attributes {
+    fn push_average(&mut self, from: &[u32]) {
+        let w: f32 = 1.0 / from.len() as f32;
+        if !self.uvs.is_empty() {
+            let (u, v) = from.iter().fold((0.0, 0.0), |(u, v), &i| {
+                let (a, b) = self.uvs[i as usize];
+                (u + w * a, v + w * b)
+            });
+            self.uvs.push((u, v));
+        }
+        if !self.colors.is_empty() {
+            let c: Vec3 = from
+                .iter()
+                .fold(Vec3::zeros(), |c, &i| c + w * self.colors[i as usize]);
+            self.colors.push(c);
+        }
+    }
+}
+
+// A mesh being subdivided. Loaders split vertices wherever the uvs or
+// normals change, so the faces refer to vertices carrying the attributes,
+// and each vertex to one of the welded `points` the subdivision rules work
+// on. Seams then move as one and don't open up.
+struct Level {
+    points: Vec<Vec3>,
+    point_of: Vec<u32>,
+    faces: Vec<Vec<u32>>,
+    attributes: Attributes,
+}
+
+// Moved old points, then one new point per edge
+fn loop_points(points: &[Vec3], faces: &[Vec<u32>], topology: &Topology) -> Vec<Vec3> {
+    let mut new_points: Vec<Vec3> = (0..points.len())
+        .map(|v| {
+            topology.boundary_vertex(v, points).unwrap_or_else(|| {
+                let n: usize = topology.vertex_edges[v].len();
+                if n == 0 {
+                    return points[v];
+                }
+                let beta: f32 = if n == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * n as f32)
+                };
+                let ring: Vec3 = topology.vertex_edges[v]
+                    .iter()
+                    .fold(Vec3::zeros(), |sum, &e| {
+                        sum + points[topology.other_end(e, v)]
+                    });
+                (1.0 - n as f32 * beta) * points[v] + beta * ring
+            })
+        })
+        .collect();
+
+    for (e, &[a, b]) in topology.edges.iter().enumerate() {
+        let (pa, pb) = (points[a as usize], points[b as usize]);
+        let p: Vec3 = if topology.is_boundary(e) {
+            0.5 * (pa + pb)
+        } else {
+            let opposite = topology.edge_faces[e]
+                .iter()
+                .fold(Vec3::zeros(), |sum, &f| {
+                    let c: u32 = *faces[f].iter().find(|&&c| c != a && c != b).unwrap();
+                    sum + points[c as usize]
+                });
+            0.375 * (pa + pb) + 0.125 * opposite
+        };
+        new_points.push(p);
+    }
+    new_points
+}
+
+// Moved old points, then one new point per edge and one per face
+fn catmull_clark_points(points: &[Vec3], faces: &[Vec<u32>], topology: &Topology) -> Vec<Vec3> {
+    let face_points: Vec<Vec3> = faces
+        .iter()
+        .map(|face| {
+            face.iter()
+                .fold(Vec3::zeros(), |sum, &v| sum + points[v as usize])
+                / face.len() as f32
+        })
+        .collect();
+
+    let mut new_points: Vec<Vec3> = (0..points.len())
+        .map(|v| {
+            topology.boundary_vertex(v, points).unwrap_or_else(|| {
+                let edges = &topology.vertex_edges[v];
+                let n: f32 = edges.len() as f32;
+                if edges.is_empty() {
+                    return points[v];
+                }
+                // each interior edge has two faces and each face around the
+                // vertex is on two of its edges
+                let (mut f, mut r) = (Vec3::zeros(), Vec3::zeros());
+                for &e in edges {
+                    for &face in &topology.edge_faces[e] {
+                        f += 0.5 * face_points[face];
+                    }
+                    r += 0.5 * (points[v] + points[topology.other_end(e, v)]);
+                }
+                (f / n + 2.0 * r / n + (n - 3.0) * points[v]) / n
+            })
+        })
+        .collect();
+
+    for (e, &[a, b]) in topology.edges.iter().enumerate() {
+        let mid: Vec3 = 0.5 * (points[a as usize] + points[b as usize]);
+        let p: Vec3 = if topology.is_boundary(e) {
+            mid
+        } else {
+            let (f0, f1) = (topology.edge_faces[e][0], topology.edge_faces[e][1]);
+            0.5 * mid + 0.25 * (face_points[f0] + face_points[f1])
+        };
+        new_points.push(p);
+    }
+    new_points.extend(face_points);
+    new_points
+}
+
+fn step(level: Level, scheme: Scheme) -> Level {
+    let Level {
+        points,
+        mut point_of,
+        faces,
+        mut attributes,
+    } = level;
+    let welded: Vec<Vec<u32>> = faces
+        .iter()
+        .map(|face| face.iter().map(|&v| point_of[v as usize]).collect())
+        .collect();
+    let shape = Topology::new(points.len(), &welded);
+    let new_points: Vec<Vec3> = match scheme {
+        Scheme::Loop => loop_points(&points, &welded, &shape),
+        Scheme::CatmullClark => catmull_clark_points(&points, &welded, &shape),
+    };
+
+    // new vertices are numbered like the new points: old ones, then one per
+    // edge, then one per face
+    let topology = Topology::new(point_of.len(), &faces);
+    let vertex_count: usize = point_of.len();
+    for &[a, b] in &topology.edges {
+        let e: usize = shape.edge(point_of[a as usize], point_of[b as usize]);
+        point_of.push((points.len() + e) as u32);
+        attributes.push_average(&[a, b]);
+    }
+    let edge_vertex = |e: usize| (vertex_count + e) as u32;
+    let mut new_faces: Vec<Vec<u32>> = Vec::new();
+    match scheme {
+        Scheme::Loop => {
+            for (face, edges) in faces.iter().zip(&topology.face_edges) {
+                let (ab, bc, ca) = (
+                    edge_vertex(edges[0]),
+                    edge_vertex(edges[1]),
+                    edge_vertex(edges[2]),
+                );
+                new_faces.push(vec![face[0], ab, ca]);
+                new_faces.push(vec![ab, face[1], bc]);
+                new_faces.push(vec![ca, bc, face[2]]);
+                new_faces.push(vec![ab, bc, ca]);
+            }
+        }
+        Scheme::CatmullClark => {
+            for (f, face) in faces.iter().enumerate() {
+                point_of.push((points.len() + shape.edges.len() + f) as u32);
+                attributes.push_average(face);
+            }
+            let face_vertex = |f: usize| (vertex_count + topology.edges.len() + f) as u32;
+            for (f, (face, edges)) in faces.iter().zip(&topology.face_edges).enumerate() {
+                let k: usize = face.len();
+                for i in 0..k {
+                    new_faces.push(vec![
+                        face[i],
+                        edge_vertex(edges[i]),
+                        face_vertex(f),
+                        edge_vertex(edges[(i + k - 1) % k]),
+                    ]);
+                }
+            }
+        }
+    }
+    Level {
+        points: new_points,
+        point_of,
+        faces: new_faces,
+        attributes,
+    }
+}
+
+// Drops corners welded onto the one before them, as where a file has two
+// copies of a point on a face. Faces left with fewer than three corners, or
+// coming back to a point, have no inside and are dropped altogether.
+fn without_repeats(face: &[u32], point_of: &[u32]) -> Option<Vec<u32>> {
+    let k: usize = face.len();
+    let face: Vec<u32> = (0..k)
+        .filter(|&i| point_of[face[i] as usize] != point_of[face[(i + k - 1) % k] as usize])
+        .map(|i| face[i])
+        .collect();
+    let mut points: Vec<u32> = face.iter().map(|&v| point_of[v as usize]).collect();
+    points.sort_unstable();
+    points.dedup();
+    if face.len() >= 3 && points.len() == face.len() {
+        Some(face)
+    } else {
+        None
+    }
+}
+
+// Smooths an imported mesh with `levels` rounds of subdivision, before it is
+// turned into a `TriangleMesh`. Catmull-Clark starts from the polygons of
+// the file when there are any. Uvs and colors are interpolated linearly;
+// normals are dropped, as they no longer match the smoothed surface, and can
+// be rebuilt with `TriangleMesh::compute_normals`.
+pub fn subdivide(data: &MeshData, scheme: Scheme, levels: usize) -> MeshData {
+    let (points, point_of) = weld(&data.positions);
+    let faces: Vec<Vec<u32>> = match scheme {
+        Scheme::Loop => data.indices.iter().map(|face| face.to_vec()).collect(),
+        Scheme::CatmullClark => data.faces(),
+    };
+    let faces: Vec<Vec<u32>> = faces
+        .iter()
+        .filter_map(|face| without_repeats(face, &point_of))
+        .collect();
+    let mut level = Level {
+        points,
+        point_of,
+        faces,
+        attributes: Attributes {
+            uvs: data.uvs.clone(),
+            colors: data.colors.clone(),
+        },
+    };
+    for _ in 0..levels {
+        level = step(level, scheme);
+    }
+
+    // fan out whatever polygons are left
+    let indices: Vec<[u32; 3]> = level
+        .faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(move |k| [face[0], face[k], face[k + 1]]))
+        .collect();
+    MeshData {
+        positions: level
+            .point_of
+            .iter()
+            .map(|&p| level.points[p as usize])
+            .collect(),
+        normals: vec![],
+        uvs: level.attributes.uvs,
+        colors: level.attributes.colors,
+        indices,
+        polygons: match scheme {
+            Scheme::Loop => vec![],
+            Scheme::CatmullClark => level.faces,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::parse_obj;
+
+    fn cube() -> MeshData {
+        let mut positions = vec![];
+        for i in 0..8 {
+            let bit = |k: usize| if i & (1 << k) != 0 { 1.0 } else { -1.0 };
+            positions.push(Vec3::new(bit(0), bit(1), bit(2)));
+        }
+        // two outward triangles per side
+        let quads: [[u32; 4]; 6] = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ];
+        let indices = quads
+            .iter()
+            .flat_map(|q| vec![[q[0], q[1], q[2]], [q[0], q[2], q[3]]])
+            .collect();
+        MeshData {
+            positions,
+            indices,
+            ..MeshData::default()
+        }
+    }
+
+    fn assert_rounder(data: &MeshData) {
+        // closed and wound outwards, and every vertex pulled in towards a
+        // sphere well inside the cube
+        let mut volume: f32 = 0.0;
+        for face in &data.indices {
+            let [a, b, c] = face.map(|i| data.positions[i as usize]);
+            volume += a.dot(&b.cross(&c)) / 6.0;
+        }
+        assert!(volume > 2.5 && volume < 8.0, "volume {}", volume);
+        for p in &data.positions {
+            assert!(p.length() > 0.5 && p.length() < 3f32.sqrt() - 0.1);
+        }
+    }
+
+    #[test]
+    fn smoothed_cube() {
+        let data = cube();
+        let smooth = subdivide(&data, Scheme::Loop, 2);
+        assert_eq!(smooth.indices.len(), 12 * 16);
+        // 8 + 18 edges, then 26 + 72 edges
+        assert_eq!(smooth.positions.len(), 98);
+        assert_rounder(&smooth);
+
+        let smooth = subdivide(&data, Scheme::CatmullClark, 2);
+        // every triangle becomes 3 quads, then each quad 4, then 2 triangles
+        assert_eq!(smooth.indices.len(), 12 * 3 * 4 * 2);
+        assert_rounder(&smooth);
+    }
+
+    #[test]
+    fn open_edges() {
+        // a flat square with colors stays flat, and its border stays straight
+        let data = MeshData {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(2.0, 2.0, 0.0),
+                Vec3::new(0.0, 2.0, 0.0),
+            ],
+            colors: vec![
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        };
+        for &scheme in &[Scheme::Loop, Scheme::CatmullClark] {
+            let smooth = subdivide(&data, scheme, 3);
+            assert_eq!(smooth.colors.len(), smooth.positions.len());
+            for (p, c) in smooth.positions.iter().zip(&smooth.colors) {
+                assert_eq!(p.z, 0.0);
+                assert!(p.x >= 0.0 && p.x <= 2.0 && p.y >= 0.0 && p.y <= 2.0);
+                if p.y == 0.0 {
+                    assert_eq!(*c, Vec3::new(1.0, 0.0, 0.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_faces() {
+        // a sliver whose first two corners are copies of the same point,
+        // alone on the diagonal it spans
+        let data = MeshData {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(2.0, 2.0, 0.0),
+                Vec3::new(0.0, 2.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 3], [1, 4, 3]],
+            ..MeshData::default()
+        };
+        let area = |data: &MeshData| -> f32 {
+            data.indices
+                .iter()
+                .map(|face| {
+                    let [a, b, c] = face.map(|i| data.positions[i as usize]);
+                    0.5 * (b - a).cross(&(c - a)).length()
+                })
+                .sum()
+        };
+        let square = MeshData {
+            indices: data.indices[..2].to_vec(),
+            ..data.clone()
+        };
+        // smooths just like the square without it
+        for &scheme in &[Scheme::Loop, Scheme::CatmullClark] {
+            let smooth = subdivide(&data, scheme, 2);
+            let expected = subdivide(&square, scheme, 2);
+            assert_eq!(smooth.indices.len(), expected.indices.len());
+            assert!((area(&smooth) - area(&expected)).abs() < 1e-5);
+        }
+    }
+
+    // a cube of quads with every side mapped to the whole texture, so that
+    // all its edges are uv seams
+    const QUAD_CUBE: &str = "
+v -1 -1 -1
+v 1 -1 -1
+v -1 1 -1
+v 1 1 -1
+v -1 -1 1
+v 1 -1 1
+v -1 1 1
+v 1 1 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+f 1/1 3/2 4/3 2/4
+f 5/1 6/2 8/3 7/4
+f 1/1 2/2 6/3 5/4
+f 3/1 7/2 8/3 4/4
+f 1/1 5/2 7/3 3/4
+f 2/1 4/2 8/3 6/4
+";
+
+    #[test]
+    fn quads_from_obj() {
+        let obj = parse_obj(QUAD_CUBE, "cube.obj").unwrap();
+        let data = obj.groups[0].mesh_data(&obj);
+        assert_eq!(data.polygons.len(), 6);
+        // corners split where the uvs differ
+        assert!(data.positions.len() > 8);
+        assert_eq!(weld(&data.positions).0.len(), 8);
+
+        let smooth = subdivide(&data, Scheme::CatmullClark, 2);
+        assert_eq!(smooth.polygons.len(), 6 * 16);
+        assert_eq!(smooth.indices.len(), 6 * 16 * 2);
+        assert_eq!(smooth.uvs.len(), smooth.positions.len());
+        assert_rounder(&smooth);
+
+        // closed across the seams: welding the corners leaves every edge
+        // with a face on each side
+        let (_, point_of) = weld(&smooth.positions);
+        let faces: Vec<Vec<u32>> = smooth
+            .polygons
+            .iter()
+            .map(|face| face.iter().map(|&v| point_of[v as usize]).collect())
+            .collect();
+        let topology = Topology::new(smooth.positions.len(), &faces);
+        assert!((0..topology.edges.len()).all(|e| !topology.is_boundary(e)));
+
+        // quads keep the symmetry of the cube, which the diagonals of a
+        // triangulated cube would break
+        for p in &smooth.positions {
+            let turned = Vec3::new(p.y, p.z, p.x);
+            assert!(smooth
+                .positions
+                .iter()
+                .any(|q| (*q - turned).length() < 1e-5));
+        }
+    }
+}
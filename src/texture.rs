@@ -1,8 +1,28 @@
+use crate::hitable::HitRecord;
 use crate::perlin::*;
 use crate::vec3::Vec3;
 
+use std::sync::Arc;
+
 pub trait Texture: Send + Sync {
     fn value(&self, u: f32, v: f32, p: &Vec3) -> Vec3;
+
+    // Lookup at a hit point. Textures that need more than the surface
+    // coordinates, like `VertexColorTexture`, override this.
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        self.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+// Lets several materials share one texture, e.g. a large image
+impl<T: Texture + ?Sized> Texture for Arc<T> {
+    fn value(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        (**self).value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        (**self).value_at(rec)
+    }
 }
 
 #[derive(Clone)]
@@ -106,6 +126,31 @@ impl Texture for ImageTexture {
     }
 }
 
+// Color interpolated from the vertices of the mesh that was hit, or of the
+// particle of a `SphereCloud`. Geometry without colors gets `fallback`.
+#[derive(Clone)]
+pub struct VertexColorTexture {
+    pub fallback: Vec3,
+}
+
+impl VertexColorTexture {
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        VertexColorTexture {
+            fallback: Vec3::new(x, y, z),
+        }
+    }
+}
+
+impl Texture for VertexColorTexture {
+    fn value(&self, _u: f32, _v: f32, _p: &Vec3) -> Vec3 {
+        self.fallback
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Vec3 {
+        rec.color.unwrap_or(self.fallback)
+    }
+}
+
 fn clamp(x: f32, max: f32) -> f32 {
     x.min(0.0).max(max)
 }
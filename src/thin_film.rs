@@ -0,0 +1,353 @@
+use crate::hitable::HitRecord;
+use crate::material::{Conductor, Dielectric, Material, ScatterRecord};
+use crate::microfacet;
+use crate::ray::Ray;
+use crate::spectrum::{self, WAVELENGTH_MAX, WAVELENGTH_MIN};
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+use std::f32::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+// Wavelengths the reflectance of white light is averaged over
+const SAMPLES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Self {
+        Complex { re, im }
+    }
+
+    fn norm_squared(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // Principal square root
+    fn sqrt(self) -> Self {
+        let r: f32 = self.norm_squared().sqrt();
+        let re: f32 = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im: f32 = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e^(i phase)
+    fn phase(phase: f32) -> Self {
+        Complex::new(phase.cos(), phase.sin())
+    }
+}
+
+impl From<f32> for Complex {
+    fn from(re: f32) -> Self {
+        Complex::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let d: f32 = other.norm_squared();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / d,
+            (self.im * other.re - self.re * other.im) / d,
+        )
+    }
+}
+
+// Reflectance of a film `thickness` nanometers thick with index `film_idx`,
+// lying between the air and a base of complex index `eta + i k`. Light
+// bouncing inside the film adds up in phase for some wavelengths and cancels
+// out for others (Airy's formula, averaged over both polarizations).
+pub fn film_reflectance(
+    cos_theta: f32,
+    film_idx: f32,
+    thickness: f32,
+    eta: f32,
+    k: f32,
+    wavelength: f32,
+) -> f32 {
+    let cos0: f32 = cos_theta.clamp(0.0, 1.0);
+    let sin2: f32 = 1.0 - cos0 * cos0;
+    let n0 = Complex::from(1.0);
+    let n1 = Complex::from(film_idx);
+    let n2 = Complex::new(eta, k);
+    // Snell's law with complex angles
+    let cos_in = |n: Complex| (Complex::from(1.0) - Complex::from(sin2) / (n * n)).sqrt();
+    let (c0, c1, c2) = (Complex::from(cos0), cos_in(n1), cos_in(n2));
+
+    let s = |na: Complex, ca: Complex, nb: Complex, cb: Complex| {
+        (na * ca - nb * cb) / (na * ca + nb * cb)
+    };
+    let p = |na: Complex, ca: Complex, nb: Complex, cb: Complex| {
+        (nb * ca - na * cb) / (nb * ca + na * cb)
+    };
+    let delay = Complex::from(4.0 * PI * thickness / wavelength) * n1 * c1;
+    // decays instead of turning when the film itself absorbs
+    let turn = Complex::phase(delay.re) * Complex::from((-delay.im).exp());
+
+    let airy = |r01: Complex, r12: Complex| {
+        ((r01 + r12 * turn) / (Complex::from(1.0) + r01 * r12 * turn)).norm_squared()
+    };
+    0.5 * (airy(s(n0, c0, n1, c1), s(n1, c1, n2, c2)) + airy(p(n0, c0, n1, c1), p(n1, c1, n2, c2)))
+}
+
+// What the film lies on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Substrate {
+    Air,        // a soap bubble, with the film seen from both sides
+    Dielectric, // light not reflected goes on into the base
+    Conductor,  // light not reflected is absorbed
+}
+
+// Iridescent film, like soap, oil or the oxide on tempered steel, over a
+// smooth `Dielectric` or a `Conductor`. The film takes over the reflection
+// off the top of the base and colors it by how thick it is: the mean of the
+// thickness texture's channels, mapped to `thickness_range` in nanometers.
+// Paths that already carry a wavelength are shaded at it, others get the
+// reflectance of white light.
+#[derive(Clone)]
+pub struct ThinFilm<M: Material, T: Texture> {
+    pub base: M,
+    pub thickness: T,
+    pub thickness_range: (f32, f32),
+    pub film_idx: f32,
+    pub eta: Vec3, // index of the base, per channel
+    pub k: Vec3,   // extinction of the base, zero for dielectrics
+    pub substrate: Substrate,
+}
+
+impl<T: Texture> ThinFilm<Dielectric, T> {
+    // Light going into the base is bent by its `refract_idx` alone, so the
+    // base can't be dispersive. Its absorption still applies inside.
+    pub fn over_dielectric(base: Dielectric, film_idx: f32, thickness: T) -> Self {
+        assert!(
+            base.dispersion.is_none(),
+            "a thin film needs a non-dispersive base"
+        );
+        let eta = Vec3::new(base.refract_idx, base.refract_idx, base.refract_idx);
+        ThinFilm {
+            base,
+            thickness,
+            thickness_range: (0.0, 1000.0),
+            film_idx,
+            eta,
+            k: Vec3::zeros(),
+            substrate: Substrate::Dielectric,
+        }
+    }
+
+    // A soap film with air on both sides
+    pub fn bubble(thickness: T) -> Self {
+        ThinFilm {
+            substrate: Substrate::Air,
+            ..ThinFilm::over_dielectric(Dielectric::new(1.0), 1.33, thickness)
+        }
+    }
+}
+
+impl<U: Texture, T: Texture> ThinFilm<Conductor<U>, T> {
+    pub fn over_conductor(base: Conductor<U>, film_idx: f32, thickness: T) -> Self {
+        ThinFilm {
+            eta: base.eta,
+            k: base.k,
+            base,
+            thickness,
+            thickness_range: (0.0, 1000.0),
+            film_idx,
+            substrate: Substrate::Conductor,
+        }
+    }
+}
+
+impl<M: Material, T: Texture> ThinFilm<M, T> {
+    pub fn with_thickness_range(mut self, min: f32, max: f32) -> Self {
+        self.thickness_range = (min, max);
+        self
+    }
+
+    // Makes the film `nm` nanometers thick all over, whatever the texture
+    pub fn with_thickness(self, nm: f32) -> Self {
+        self.with_thickness_range(nm, nm)
+    }
+
+    // The base's index at a wavelength, interpolated between the blue,
+    // green and red channels
+    fn base_index(&self, wavelength: f32) -> (f32, f32) {
+        let t: f32 = ((wavelength - 450.0) / 100.0).clamp(0.0, 2.0);
+        let (i, f) = if t < 1.0 { (2, t) } else { (1, t - 1.0) };
+        let lerp = |v: &Vec3| v[i] + f * (v[i - 1] - v[i]);
+        (lerp(&self.eta), lerp(&self.k))
+    }
+
+    // Reflectance as a color for white light, or as a gray level for a
+    // single wavelength
+    fn reflectance(&self, cos_theta: f32, rec: &HitRecord, wavelength: Option<f32>) -> Vec3 {
+        let t: Vec3 = self.thickness.value_at(rec);
+        let (min, max) = self.thickness_range;
+        let thickness: f32 = min + (t.x + t.y + t.z) / 3.0 * (max - min);
+        let at = |wavelength: f32| {
+            let (eta, k) = self.base_index(wavelength);
+            film_reflectance(cos_theta, self.film_idx, thickness, eta, k, wavelength)
+        };
+        match wavelength {
+            Some(wavelength) => {
+                let r: f32 = at(wavelength);
+                Vec3::new(r, r, r)
+            }
+            None => {
+                // normalized so that a flat reflectance stays gray
+                let mut sum = Vec3::zeros();
+                let mut white = Vec3::zeros();
+                for i in 0..SAMPLES {
+                    let w: f32 = WAVELENGTH_MIN
+                        + (i as f32 + 0.5) / SAMPLES as f32 * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+                    let rgb: Vec3 = spectrum::wavelength_to_rgb(w);
+                    sum += at(w) * rgb;
+                    white += rgb;
+                }
+                Vec3::new(sum.x / white.x, sum.y / white.y, sum.z / white.z)
+            }
+        }
+    }
+}
+
+impl<M: Material, T: Texture> Material for ThinFilm<M, T> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+        let inside: bool = r_in.direction.dot(&rec.normal) > 0.0;
+        // only a bubble has film on its inside too
+        if inside && self.substrate != Substrate::Air {
+            return self.base.scatter(r_in, rec);
+        }
+        let normal: Vec3 = if inside { -rec.normal } else { rec.normal };
+        let unit_direction: Vec3 = r_in.direction.unit_vector();
+        let cos_theta: f32 = -unit_direction.dot(&normal);
+        let reflectance: Vec3 = self.reflectance(cos_theta, rec, r_in.wavelength);
+
+        if self.substrate == Substrate::Conductor {
+            // keep the base's rough reflection and swap its Fresnel term,
+            // taken at the surface normal, for the film's
+            let scatter: ScatterRecord = self.base.scatter(r_in, rec)?;
+            let fresnel: Vec3 = microfacet::fresnel_conductor(cos_theta, &self.eta, &self.k);
+            return Some(ScatterRecord {
+                attenuation: scatter.attenuation
+                    * Vec3::new(
+                        reflectance.x / fresnel.x,
+                        reflectance.y / fresnel.y,
+                        reflectance.z / fresnel.z,
+                    ),
+                ..scatter
+            });
+        }
+
+        // reflect in proportion to the mean reflectance, and weight each
+        // choice to give back the right color
+        let p: f32 =
+            ((reflectance.x + reflectance.y + reflectance.z) / 3.0).clamp(1e-3, 1.0 - 1e-3);
+        let (attenuation, direction) = if rand::random::<f32>() < p {
+            (reflectance / p, unit_direction + 2.0 * cos_theta * normal)
+        } else {
+            // the film's faces are parallel, so only the base bends the ray,
+            // as a smooth dielectric would
+            let eta: f32 = if inside { 1.0 } else { self.eta.x };
+            let sin2_t: f32 = (1.0 - cos_theta * cos_theta) / (eta * eta);
+            let cos_t: f32 = (1.0 - sin2_t).max(0.0).sqrt();
+            (
+                (Vec3::new(1.0, 1.0, 1.0) - reflectance) / (1.0 - p),
+                unit_direction / eta + (cos_theta / eta - cos_t) * normal,
+            )
+        };
+        Some(ScatterRecord {
+            attenuation,
+            scattered: Ray::new(rec.p, direction, r_in.time),
+        })
+    }
+
+    fn emitted(&self, u: f32, v: f32, p: &Vec3) -> Vec3 {
+        self.base.emitted(u, v, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn without_film() {
+        // a film of no thickness leaves the base's own Fresnel term
+        for &cos in &[1.0, 0.7, 0.2] {
+            let glass: f32 = film_reflectance(cos, 1.33, 0.0, 1.5, 0.0, 550.0);
+            assert!((glass - microfacet::fresnel_dielectric(cos, 1.5)).abs() < 1e-4);
+            let eta = Vec3::new(0.2, 0.4, 1.4);
+            let k = Vec3::new(3.6, 2.4, 1.8);
+            let gold: f32 = film_reflectance(cos, 1.8, 0.0, eta.x, k.x, 650.0);
+            assert!((gold - microfacet::fresnel_conductor(cos, &eta, &k).x).abs() < 1e-4);
+        }
+        // and light goes through a bubble at the right angle
+        let r: f32 = film_reflectance(1.0, 1.33, 0.0, 1.0, 0.0, 550.0);
+        assert!(r.abs() < 1e-6);
+    }
+
+    #[test]
+    fn iridescence() {
+        // quarter-wave film: the reflections off both faces add up for the
+        // wavelength four times the optical thickness, and a half-wave
+        // film is invisible
+        let thickness: f32 = 550.0 / (4.0 * 1.33);
+        let peak: f32 = film_reflectance(1.0, 1.33, thickness, 1.0, 0.0, 550.0);
+        let r01: f32 = (0.33f32 / 2.33).powi(2);
+        assert!(peak > 3.5 * r01);
+        let hidden: f32 = film_reflectance(1.0, 1.33, 2.0 * thickness, 1.0, 0.0, 550.0);
+        assert!(hidden.abs() < 1e-4);
+
+        // the color of a soap bubble changes with its thickness
+        let color = |nm: f32| {
+            let bubble = ThinFilm::bubble(ConstantTexture::new(0.0, 0.0, 0.0)).with_thickness(nm);
+            let rec = HitRecord {
+                t: 1.0,
+                u: 0.0,
+                v: 0.0,
+                p: Vec3::zeros(),
+                normal: Vec3::new(0.0, 0.0, 1.0),
+                color: None,
+                material: &bubble,
+            };
+            let c: Vec3 = bubble.reflectance(1.0, &rec, None);
+            c / (c.x + c.y + c.z)
+        };
+        let (a, b) = (color(300.0), color(450.0));
+        assert!((a - b).length() > 0.1);
+    }
+}
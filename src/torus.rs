@@ -0,0 +1,190 @@
+use crate::aabb::AABB;
+use crate::disk::azimuth;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+use std::f32::consts::PI;
+
+// Ring around the y axis: a tube of `minor_radius` following a circle of
+// `major_radius` in the xz plane
+pub struct Torus<M: Material> {
+    pub center: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub phi_max: f32, // sweep in radians
+    pub material: M,
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(center: Vec3, major_radius: f32, minor_radius: f32, material: M) -> Self {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            phi_max: 2.0 * PI,
+            material,
+        }
+    }
+
+    // Keeps only the slice from +x around to `angle` degrees. The cut ends
+    // of the tube are left open.
+    pub fn with_sweep(mut self, angle: f32) -> Self {
+        self.phi_max = angle.to_radians().min(2.0 * PI);
+        self
+    }
+}
+
+impl<M: Material> Hitable for Torus<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (big_r, small_r) = (self.major_radius as f64, self.minor_radius as f64);
+        let o: Vec3 = r.origin - self.center;
+        let d: Vec3 = r.direction;
+
+        // only look inside the bounding sphere, starting the polynomial from
+        // where the ray enters it so that the coefficients stay well scaled
+        let bound: f32 = self.major_radius + self.minor_radius;
+        let a: f32 = d.dot(&d);
+        let b: f32 = o.dot(&d);
+        let discriminant: f32 = b * b - a * (o.dot(&o) - bound * bound);
+        if discriminant <= 0.0 {
+            return None;
+        }
+        let t_enter: f32 = (-b - discriminant.sqrt()) / a;
+        let t_exit: f32 = (-b + discriminant.sqrt()) / a;
+        let (lo, hi) = (t_min.max(t_enter), t_max.min(t_exit));
+        if lo >= hi {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2) along p = o + s d
+        let o: Vec3 = o + t_enter * d;
+        let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        let dd: f64 = dx * dx + dy * dy + dz * dz;
+        let od: f64 = ox * dx + oy * dy + oz * dz;
+        let k: f64 = ox * ox + oy * oy + oz * oz + big_r * big_r - small_r * small_r;
+        let q: f64 = 4.0 * big_r * big_r;
+        let coefs = [
+            k * k - q * (ox * ox + oz * oz),
+            4.0 * od * k - 2.0 * q * (ox * dx + oz * dz),
+            4.0 * od * od + 2.0 * dd * k - q * (dx * dx + dz * dz),
+            4.0 * dd * od,
+            dd * dd,
+        ];
+        let roots = real_roots(&coefs, (lo - t_enter) as f64, (hi - t_enter) as f64);
+
+        roots.into_iter().find_map(|s| {
+            let t: f32 = t_enter + s as f32;
+            let p: Vec3 = o + (s as f32) * d;
+            let phi: f32 = azimuth(p.x, p.z);
+            if t <= t_min || t >= t_max || phi > self.phi_max {
+                return None;
+            }
+            // the closest point on the center circle of the tube
+            let dist: f32 = (p.x * p.x + p.z * p.z).sqrt();
+            let ring: Vec3 = Vec3::new(p.x, 0.0, p.z) * (self.major_radius / dist);
+            let theta: f32 = p.y.atan2(dist - self.major_radius);
+            Some(HitRecord {
+                t,
+                u: phi / self.phi_max,
+                v: (theta + PI) / (2.0 * PI),
+                p: r.point_at_parameter(t),
+                normal: (p - ring).unit_vector(),
+                color: None,
+                material: &self.material,
+            })
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        let outer: f32 = self.major_radius + self.minor_radius;
+        let half = Vec3::new(outer, self.minor_radius, outer);
+        Some(AABB::new(self.center - half, self.center + half))
+    }
+}
+
+fn eval(coefs: &[f64], x: f64) -> f64 {
+    coefs.iter().rev().fold(0.0, |acc, &c| acc * x + c)
+}
+
+// Real roots in [lo, hi] of the polynomial with `coefs` (constant first),
+// smallest first. The roots of the derivative split the range into pieces
+// where the polynomial is monotonic, so each piece holds at most one root,
+// found by bisection. Roots that only touch zero are missed, which for a
+// torus means grazing rays.
+fn real_roots(coefs: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    if coefs.len() == 2 {
+        let x: f64 = -coefs[0] / coefs[1];
+        return if x >= lo && x <= hi { vec![x] } else { vec![] };
+    }
+    let derivative: Vec<f64> = coefs
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &c)| i as f64 * c)
+        .collect();
+    let mut bounds: Vec<f64> = vec![lo];
+    bounds.extend(real_roots(&derivative, lo, hi));
+    bounds.push(hi);
+
+    let mut roots = Vec::new();
+    for w in bounds.windows(2) {
+        let (mut a, mut b) = (w[0], w[1]);
+        let fa: f64 = eval(coefs, a);
+        if (fa < 0.0) == (eval(coefs, b) < 0.0) {
+            continue;
+        }
+        for _ in 0..64 {
+            let mid: f64 = 0.5 * (a + b);
+            if (eval(coefs, mid) < 0.0) == (fa < 0.0) {
+                a = mid;
+            } else {
+                b = mid;
+            }
+        }
+        roots.push(0.5 * (a + b));
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn ring_and_hole() {
+        let white = Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5));
+        let torus = Torus::new(Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5, white);
+
+        // along x, the outside of the tube
+        let r = Ray::new(Vec3::new(-10.0, 1.0, 0.0), Vec3::new(2.0, 0.0, 0.0), 0.0);
+        let rec = torus.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 3.75).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!((rec.u - 0.5).abs() < 1e-4);
+
+        // from inside the hole, the inner side of the tube
+        let r = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let rec = torus.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 1.5).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        // straight down through the hole and from the top onto the tube
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let r = Ray::new(Vec3::new(0.0, 5.0, 0.0), down, 0.0);
+        assert!(torus.hit(&r, 0.001, f32::MAX).is_none());
+        let r = Ray::new(Vec3::new(0.0, 5.0, 2.0), down, 0.0);
+        let rec = torus.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 3.5).abs() < 1e-4);
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!((rec.v - 0.75).abs() < 1e-4);
+    }
+}
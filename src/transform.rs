@@ -0,0 +1,188 @@
+use crate::aabb::AABB;
+use crate::hitable::{HitRecord, Hitable, Interval, Solid};
+use crate::matrix::Matrix4;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+// Places a hitable with an arbitrary affine transform. The builder methods
+// apply on top of what is already there, so
+// `Transform::new(h).rotate_y(30.0).translate(v)` rotates first and then
+// moves the rotated object.
+pub struct Transform<H: Hitable> {
+    pub hitable: H,
+    pub matrix: Matrix4,        // object space to world space
+    pub inverse: Matrix4,       // world space to object space
+    pub normal_matrix: Matrix4, // inverse transpose of `matrix`
+}
+
+impl<H: Hitable> Transform<H> {
+    pub fn new(hitable: H) -> Self {
+        Transform::with_matrix(hitable, Matrix4::identity())
+    }
+
+    pub fn with_matrix(hitable: H, matrix: Matrix4) -> Self {
+        let inverse = matrix
+            .inverse()
+            .expect("transform matrix is not invertible");
+        Transform {
+            hitable,
+            matrix,
+            inverse,
+            normal_matrix: inverse.transpose(),
+        }
+    }
+
+    pub fn then(self, matrix: Matrix4) -> Self {
+        let combined = matrix * self.matrix;
+        Transform::with_matrix(self.hitable, combined)
+    }
+
+    pub fn translate(self, offset: Vec3) -> Self {
+        self.then(Matrix4::translation(offset))
+    }
+
+    pub fn scale(self, factor: Vec3) -> Self {
+        self.then(Matrix4::scaling(factor))
+    }
+
+    pub fn rotate(self, axis: Vec3, angle: f32) -> Self {
+        self.then(Matrix4::rotation(axis, angle))
+    }
+
+    pub fn rotate_x(self, angle: f32) -> Self {
+        self.then(Matrix4::rotation_x(angle))
+    }
+
+    pub fn rotate_y(self, angle: f32) -> Self {
+        self.then(Matrix4::rotation_y(angle))
+    }
+
+    pub fn rotate_z(self, angle: f32) -> Self {
+        self.then(Matrix4::rotation_z(angle))
+    }
+
+    pub fn shear(self, xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Self {
+        self.then(Matrix4::shear(xy, xz, yx, yz, zx, zy))
+    }
+
+    // the direction is not normalized, so `t` is the same in both spaces
+    fn local_ray(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.inverse.transform_point(&r.origin),
+            self.inverse.transform_vector(&r.direction),
+            r.time,
+        )
+    }
+
+    fn to_world<'a>(&self, r: &Ray, mut rec: HitRecord<'a>) -> HitRecord<'a> {
+        rec.p = r.point_at_parameter(rec.t);
+        rec.normal = self
+            .normal_matrix
+            .transform_vector(&rec.normal)
+            .unit_vector();
+        rec
+    }
+}
+
+impl<H: Hitable> Hitable for Transform<H> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let rec = self.hitable.hit(&self.local_ray(r), t_min, t_max)?;
+        Some(self.to_world(r, rec))
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<AABB> {
+        let bbox = self.hitable.bounding_box(t0, t1)?;
+        Some(transform_box(&self.matrix, &bbox))
+    }
+}
+
+impl<H: Solid> Solid for Transform<H> {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        self.hitable
+            .intervals(&self.local_ray(r))
+            .into_iter()
+            .map(|i| Interval {
+                enter: self.to_world(r, i.enter),
+                exit: self.to_world(r, i.exit),
+            })
+            .collect()
+    }
+}
+
+// Box around the eight transformed corners of `bbox`
+pub fn transform_box(matrix: &Matrix4, bbox: &AABB) -> AABB {
+    let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Vec3::new(-f32::MAX, -f32::MAX, -f32::MAX);
+    for i in 0..8 {
+        let corner = Vec3::new(
+            if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+            if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+            if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+        );
+        let p = matrix.transform_point(&corner);
+        for c in 0..3 {
+            min[c] = min[c].min(p[c]);
+            max[c] = max[c].max(p[c]);
+        }
+    }
+    AABB::new(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::texture::ConstantTexture;
+
+    fn white() -> Lambertian<ConstantTexture> {
+        Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5))
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotated_block() {
+        // a unit cube turned 90 degrees around x, then moved up
+        let block = Transform::new(Block::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 2.0, 3.0),
+            white(),
+        ))
+        .rotate_x(90.0)
+        .translate(Vec3::new(0.0, 5.0, 0.0));
+
+        let bbox = block.bounding_box(0.0, 1.0).unwrap();
+        assert_near(bbox.min, Vec3::new(0.0, 2.0, 0.0));
+        assert_near(bbox.max, Vec3::new(1.0, 5.0, 2.0));
+
+        let r = Ray::new(Vec3::new(0.5, 10.0, 1.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        let rec = block.hit(&r, 0.001, f32::MAX).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-4);
+        assert_near(rec.p, Vec3::new(0.5, 5.0, 1.0));
+        assert_near(rec.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn scaled_sphere_normal() {
+        // an ellipsoid twice as wide along x
+        let ellipsoid = Transform::new(Sphere::new(Vec3::zeros(), 1.0, white()))
+            .scale(Vec3::new(2.0, 1.0, 1.0));
+        let r = Ray::new(
+            Vec3::new(2.0_f32.sqrt(), 10.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let rec = ellipsoid.hit(&r, 0.001, f32::MAX).unwrap();
+        let y = 0.5_f32.sqrt();
+        assert_near(rec.p, Vec3::new(2.0_f32.sqrt(), y, 0.0));
+        // gradient of x^2 / 4 + y^2 + z^2
+        assert_near(
+            rec.normal,
+            Vec3::new(2.0_f32.sqrt() / 2.0, 2.0 * y, 0.0).unit_vector(),
+        );
+    }
+}
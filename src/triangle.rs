@@ -0,0 +1,176 @@
+use crate::aabb::*;
+use crate::hitable::{HitRecord, Hitable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+pub struct Triangle<M: Material> {
+    pub vertices: [Vec3; 3],
+    pub normals: Option<[Vec3; 3]>, // per-vertex shading normals
+    pub uvs: Option<[(f32, f32); 3]>,
+    pub material: M,
+}
+
+impl<M: Material> Triangle<M> {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: M) -> Self {
+        Triangle {
+            vertices: [v0, v1, v2],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f32, f32); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl<M: Material> Hitable for Triangle<M> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let [v0, v1, v2] = self.vertices;
+        let (t, b1, b2) = intersect_triangle(r, &v0, &v1, &v2, t_min, t_max)?;
+        let normal = match &self.normals {
+            Some(n) => interpolate(&n[0], &n[1], &n[2], b1, b2).unit_vector(),
+            None => (v1 - v0).cross(&(v2 - v0)).unit_vector(),
+        };
+        let (u, v) = match &self.uvs {
+            Some(uv) => interpolate_uv(uv, b1, b2),
+            None => (b1, b2),
+        };
+        Some(HitRecord {
+            t,
+            u,
+            v,
+            p: r.point_at_parameter(t),
+            normal,
+            color: None,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<AABB> {
+        Some(triangle_box(
+            &self.vertices[0],
+            &self.vertices[1],
+            &self.vertices[2],
+        ))
+    }
+}
+
+// Möller–Trumbore ray/triangle intersection. Returns `t` and the barycentric
+// coordinates of the hit point with respect to `v1` and `v2`.
+pub fn intersect_triangle(
+    r: &Ray,
+    v0: &Vec3,
+    v1: &Vec3,
+    v2: &Vec3,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let e1: Vec3 = *v1 - *v0;
+    let e2: Vec3 = *v2 - *v0;
+    let pvec: Vec3 = r.direction.cross(&e2);
+    let det: f32 = e1.dot(&pvec);
+    if det.abs() < 1e-8 {
+        // ray is parallel to the triangle
+        return None;
+    }
+    let inv_det: f32 = 1.0 / det;
+    let tvec: Vec3 = r.origin - *v0;
+    let b1: f32 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+    let qvec: Vec3 = tvec.cross(&e1);
+    let b2: f32 = r.direction.dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+    let t: f32 = e2.dot(&qvec) * inv_det;
+    if t < t_min || t > t_max {
+        None
+    } else {
+        Some((t, b1, b2))
+    }
+}
+
+pub fn interpolate(a0: &Vec3, a1: &Vec3, a2: &Vec3, b1: f32, b2: f32) -> Vec3 {
+    (1.0 - b1 - b2) * *a0 + b1 * *a1 + b2 * *a2
+}
+
+pub fn interpolate_uv(uv: &[(f32, f32); 3], b1: f32, b2: f32) -> (f32, f32) {
+    let b0: f32 = 1.0 - b1 - b2;
+    (
+        b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+        b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+    )
+}
+
+// Bounding box of a triangle, padded so that it doesn't have zero width when
+// the triangle lies in an axis-aligned plane
+pub fn triangle_box(v0: &Vec3, v1: &Vec3, v2: &Vec3) -> AABB {
+    let bbox = surrounding_box(
+        surrounding_box(AABB::new(*v0, *v0), AABB::new(*v1, *v1)),
+        AABB::new(*v2, *v2),
+    );
+    let pad = Vec3::new(0.0001, 0.0001, 0.0001);
+    AABB::new(bbox.min - pad, bbox.max + pad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::ConstantTexture;
+
+    fn triangle() -> Triangle<Lambertian<ConstantTexture>> {
+        Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Lambertian::new(ConstantTexture::new(0.5, 0.5, 0.5)),
+        )
+    }
+
+    #[test]
+    fn hit_inside() {
+        let tri = triangle();
+        let r = Ray::new(Vec3::new(0.25, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let rec = tri.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.p, Vec3::new(0.25, 0.5, 0.0));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!((rec.u, rec.v), (0.25, 0.5));
+    }
+
+    #[test]
+    fn miss_outside() {
+        let tri = triangle();
+        let r = Ray::new(Vec3::new(0.75, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        assert!(tri.hit(&r, 0.001, f32::MAX).is_none());
+        let parallel = Ray::new(Vec3::new(-1.0, 0.2, 0.0), Vec3::new(1.0, 0.0, 0.0), 0.0);
+        assert!(tri.hit(&parallel, 0.001, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn interpolated_uvs_and_normals() {
+        let tri = triangle()
+            .with_uvs([(0.0, 0.0), (0.0, 1.0), (1.0, 1.0)])
+            .with_normals([
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.0, 1.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ]);
+        let r = Ray::new(Vec3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        let rec = tri.hit(&r, 0.001, f32::MAX).unwrap();
+        assert_eq!((rec.u, rec.v), (0.5, 1.0));
+        assert_eq!(rec.normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+}